@@ -1,12 +1,38 @@
-use astal::{prelude::ApplicationExt, subclass::prelude::*};
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use astal::subclass::prelude::*;
+use astal_cava::prelude::*;
 use astal_io::prelude::ApplicationExt as AstalIOApplicationExt;
+use astal_notifd::prelude::*;
 use gtk::{
+    gdk::{self, Monitor},
     gio,
     glib::{self, Object},
     prelude::*,
     subclass::prelude::*,
 };
 use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{error, warn};
+
+use crate::{
+    client,
+    config::APP_ID,
+    events::{self, Event},
+    ipc::{self, Command, Query, Reply},
+    launcher::Launcher,
+    logging,
+    notifications::Notifications,
+    registry::{Output, Registry},
+    right::Right,
+    services::{Service, Services},
+    session::Session,
+    status, style,
+    top::Top,
+    user_config::{self, UserConfig},
+};
 
 glib::wrapper! {
     pub struct App(ObjectSubclass<imp::App>)
@@ -14,19 +40,57 @@ glib::wrapper! {
         @implements gio::ActionGroup, gio::ActionMap, astal_io::Application;
 }
 
+#[derive(Debug)]
 pub enum Message {
-    Toggle { component: Component },
-    On { component: Component },
-    Off { component: Component },
-    HideLauncher,
+    Toggle {
+        component: Component,
+        target: Target,
+    },
+    On {
+        component: Component,
+        target: Target,
+    },
+    Off {
+        component: Component,
+        target: Target,
+    },
+    /// Hides the launcher window once it has finished animating out
+    HideLauncher { launcher: Launcher },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Component {
     Top,
     Right,
+    Notifications,
     Launcher,
 }
 
+impl Component {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Component::Top => "top",
+            Component::Right => "right",
+            Component::Notifications => "notifications",
+            Component::Launcher => "launcher",
+        }
+    }
+}
+
+/// Where the settings of named profiles are stored, one directory per profile
+const PROFILES_PATH: &str = "/in/wobbl/commashell/profiles/";
+
+/// Which monitors a message applies to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Target {
+    #[default]
+    All,
+    /// The monitor the user is currently interacting with
+    Focused,
+    /// A monitor by its connector name, e.g. `DP-2`
+    Connector(String),
+}
+
 impl App {
     pub fn new() -> Self {
         Object::builder().build()
@@ -36,55 +100,638 @@ impl App {
         AppBuilder::new()
     }
 
+    /// The settings of this instance's profile, or the default settings if it has none
+    pub fn settings(&self) -> gio::Settings {
+        match self.settings_profile() {
+            Some(profile) => gio::Settings::with_path(
+                &format!("{APP_ID}.profile"),
+                &format!("{PROFILES_PATH}{profile}/"),
+            ),
+            None => gio::Settings::new(APP_ID),
+        }
+    }
+
+    /// Creates the shell windows enabled on `monitor`
+    pub fn add_output(&self, monitor: &Monitor) {
+        let mut output = Output::new(monitor);
+        let connector = output.connector();
+        let components = self.config().components.clone();
+        let shown =
+            |layout: &user_config::Layout| layout.shown_on(connector.as_ref().map(|c| c.as_str()));
+
+        if shown(&components.top) {
+            let top = Top::new(self, monitor);
+            self.add_window(&top);
+            output.insert(Component::Top, &top);
+        }
+
+        if shown(&components.right) {
+            let right = Right::new(self, monitor);
+            self.add_window(&right);
+            output.insert(Component::Right, &right);
+        }
+
+        if shown(&components.notifications) {
+            let notifications = Notifications::new(self, monitor);
+            self.add_window(&notifications);
+            output.insert(Component::Notifications, &notifications);
+        }
+
+        if shown(&components.launcher) {
+            let launcher = Launcher::new(self, monitor);
+            self.add_window(&launcher);
+            output.insert(Component::Launcher, &launcher);
+        }
+
+        // put things back the way they were if this monitor was connected before
+        if let Some(state) = connector.and_then(|connector| {
+            self.imp()
+                .session
+                .borrow()
+                .outputs
+                .get(connector.as_str())
+                .cloned()
+        }) {
+            if let Ok(top) = output.top() {
+                top.set_reveal(state.top);
+
+                if let Some(wallpaper) = state.wallpaper {
+                    top.set_wallpaper(wallpaper.to_string_lossy().to_string());
+                }
+            }
+
+            if let Ok(right) = output.right() {
+                right.set_reveal(state.right);
+
+                if let Some(player) = state.player {
+                    right.restore_player(player);
+                }
+            }
+
+            if let Ok(notifications) = output.notifications() {
+                notifications.restore(&state.notifications);
+            }
+        }
+
+        // the session keeps what each window was showing
+        if let Ok(top) = output.top() {
+            top.connect_reveal_notify(glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move |_top| app.save_session_later()
+            ));
+            top.connect_wallpaper_notify(glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move |_top| app.save_session_later()
+            ));
+        }
+
+        if let Ok(right) = output.right() {
+            right.connect_reveal_notify(glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move |_right| app.save_session_later()
+            ));
+            right.connect_player_notify(glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move |_right| app.save_session_later()
+            ));
+        }
+
+        events::watch_output(self, &output);
+        self.emit(Event::Monitor {
+            connector: output.connector().map(|c| c.to_string()),
+            connected: true,
+        });
+        self.imp().registry.borrow_mut().insert(output);
+        self.save_session_later();
+    }
+
+    /// Destroys the shell windows of a disconnected monitor, remembering what was revealed
+    pub fn remove_output(&self, monitor: &Monitor) {
+        let Some(output) = self.imp().registry.borrow_mut().remove(monitor) else {
+            return;
+        };
+
+        if let Some(connector) = output.connector() {
+            self.imp()
+                .session
+                .borrow_mut()
+                .outputs
+                .insert(connector.to_string(), output.state());
+        }
+
+        if let Ok(launcher) = output.launcher() {
+            if self.imp().open_launcher.borrow().as_ref() == Some(&launcher) {
+                self.imp().open_launcher.replace(None);
+                self.set_launcher_reveal(false);
+            }
+        }
+
+        for window in output.windows() {
+            window.close();
+        }
+
+        self.emit(Event::Monitor {
+            connector: output.connector().map(|c| c.to_string()),
+            connected: false,
+        });
+        self.save_session_later();
+    }
+
+    /// Checks which optional backends can be used, before any window needs them
+    pub async fn detect_services(&self) {
+        let services = Services::detect().await;
+        for service in Service::ALL {
+            if let Some(reason) = services.unavailable(service) {
+                warn!(service = service.name(), reason, "Service is unavailable");
+            }
+        }
+
+        self.imp().services.replace(services);
+    }
+
+    /// Why `service` can't be used, or None if it can
+    pub fn unavailable(&self, service: Service) -> Option<String> {
+        self.imp()
+            .services
+            .borrow()
+            .unavailable(service)
+            .map(str::to_string)
+    }
+
+    /// The current contents of the config file
+    pub fn config(&self) -> Rc<UserConfig> {
+        self.imp().config.borrow().clone()
+    }
+
+    /// Loads the config file, and reloads it whenever it changes
+    pub fn watch_config(&self) {
+        let path = UserConfig::path();
+        self.reload_config(&path);
+        self.watch_file(&UserConfig::path(), move |app| app.reload_config(&path));
+    }
+
+    /// Applies the stylesheets, and re-applies them whenever one changes or the shell
+    /// switches between light and dark
+    pub fn watch_css(&self) {
+        let reload = |app: &App| {
+            if let Err(err) = app.reload_css() {
+                error!(%err, "Could not reload stylesheets");
+            }
+        };
+
+        style::follow_color_scheme(&self.settings());
+        reload(self);
+
+        for path in style::paths() {
+            self.watch_file(&path, reload);
+        }
+
+        adw::StyleManager::default().connect_dark_notify(glib::clone!(
+            #[weak(rename_to = app)]
+            self,
+            move |_style_manager| reload(&app)
+        ));
+    }
+
+    /// Replaces the applied stylesheets with what's on disk, keeping them if a file can't be read
+    pub fn reload_css(&self) -> Result<(), ipc::Error> {
+        style::apply(self)
+    }
+
+    /// Calls `on_change` whenever the file at `path` is written, created or deleted
+    fn watch_file(&self, path: &Path, on_change: impl Fn(&App) + 'static) {
+        let monitor = match gio::File::for_path(path)
+            .monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE)
+        {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                error!(path = %path.display(), %err, "Could not watch file");
+                return;
+            }
+        };
+
+        monitor.connect_changed(glib::clone!(
+            #[weak(rename_to = app)]
+            self,
+            move |_monitor, _file, _other_file, event| {
+                if matches!(
+                    event,
+                    gio::FileMonitorEvent::ChangesDoneHint
+                        | gio::FileMonitorEvent::Created
+                        | gio::FileMonitorEvent::Deleted
+                ) {
+                    on_change(&app);
+                }
+            }
+        ));
+
+        self.imp().file_monitors.borrow_mut().push(monitor);
+    }
+
+    /// Applies the config at `path`, keeping the current one if it's invalid
+    fn reload_config(&self, path: &Path) {
+        let old = match UserConfig::reload(&self.imp().config, path) {
+            Ok(old) => old,
+            Err(err) => {
+                error!(
+                    path = %path.display(),
+                    %err,
+                    "Invalid config, keeping the previous one"
+                );
+                return;
+            }
+        };
+
+        logging::apply_config(&old.log, &self.config().log);
+
+        // windows are placed when they're created, so start over on monitors whose layout changed
+        for output in self.outputs() {
+            let connector = output.connector();
+            if old
+                .components
+                .changed_on(&self.config().components, connector.as_deref())
+            {
+                self.remove_output(&output.monitor);
+                self.add_output(&output.monitor);
+            }
+        }
+
+        // the clock and launcher read the config as they go, everything else needs a nudge
+        if let Some(cava) = astal_cava::functions::default() {
+            cava.set_bars(self.config().cava.bars);
+            cava.set_framerate(self.config().cava.framerate);
+        }
+
+        for output in self.outputs() {
+            if let Ok(right) = output.right() {
+                right.select_player();
+            }
+        }
+    }
+
+    fn session_path(&self) -> PathBuf {
+        let instance = self.instance_name();
+        Session::path(
+            instance
+                .as_ref()
+                .map_or(client::DEFAULT_INSTANCE, |instance| instance.as_str()),
+        )
+    }
+
+    /// Reads the state saved by the last run, to be restored as windows are created, and saves it
+    /// again whenever it changes
+    pub fn load_session(&self) {
+        let path = self.session_path();
+        match Session::load(&path) {
+            Ok(session) => {
+                self.imp().session.replace(session);
+            }
+            Err(err) => error!(path = %path.display(), %err, "Could not read session state"),
+        }
+
+        // state that isn't kept by a monitor's windows
+        self.connect_launcher_reveal_notify(|app| app.save_session_later());
+
+        if let Some(notifd) = astal_notifd::functions::default() {
+            notifd.connect_notified(glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move |_notifd, _id, _replaced| app.save_session_later()
+            ));
+            notifd.connect_resolved(glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move |_notifd, _id, _reason| app.save_session_later()
+            ));
+        }
+    }
+
+    /// Opens the launcher again if it was open when the shell last stopped
+    pub fn restore_launcher(&self) {
+        let Some(connector) = self.imp().session.borrow().launcher.clone() else {
+            return;
+        };
+
+        if let Err(err) = self.enable_launcher(&Target::Connector(connector)) {
+            warn!(%err, "Could not reopen the launcher");
+        }
+    }
+
+    /// Writes the current state to disk
+    pub fn save_session(&self) {
+        if let Some(source) = self.imp().pending_save.take() {
+            source.remove();
+        }
+
+        let open_launcher = self.imp().open_launcher.borrow().clone();
+        let session = {
+            let mut session = self.imp().session.borrow_mut();
+
+            for output in self.outputs() {
+                if let Some(connector) = output.connector() {
+                    session
+                        .outputs
+                        .insert(connector.to_string(), output.state());
+                }
+            }
+
+            session.launcher = open_launcher.and_then(|launcher| {
+                self.outputs()
+                    .into_iter()
+                    .find(|output| output.launcher().is_ok_and(|l| l == launcher))
+                    .and_then(|output| output.connector())
+                    .map(|connector| connector.to_string())
+            });
+
+            session.clone()
+        };
+
+        let path = self.session_path();
+        if let Err(err) = session.save(&path) {
+            error!(path = %path.display(), %err, "Could not save session state");
+        }
+    }
+
+    /// Saves the session once things have settled down, so a burst of changes is written once
+    fn save_session_later(&self) {
+        if self.imp().pending_save.borrow().is_some() {
+            return;
+        }
+
+        let source = glib::timeout_add_local_once(
+            std::time::Duration::from_secs(1),
+            glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                move || {
+                    app.imp().pending_save.take();
+                    app.save_session();
+                }
+            ),
+        );
+
+        self.imp().pending_save.replace(Some(source));
+    }
+
+    /// Creates windows for every monitor, and keeps them in sync as monitors are plugged in and out
+    pub fn watch_monitors(&self) {
+        let Some(display) = gdk::Display::default() else {
+            error!("No display to watch monitors on");
+            return;
+        };
+
+        let monitors = display.monitors();
+        self.sync_outputs(&monitors);
+
+        monitors.connect_items_changed(glib::clone!(
+            #[weak(rename_to = app)]
+            self,
+            move |monitors, _position, _removed, _added| app.sync_outputs(monitors)
+        ));
+    }
+
+    fn sync_outputs(&self, monitors: &gio::ListModel) {
+        let monitors: Vec<Monitor> = monitors.iter::<Monitor>().filter_map(Result::ok).collect();
+
+        for output in self.outputs() {
+            if !monitors.contains(&output.monitor) {
+                self.remove_output(&output.monitor);
+            }
+        }
+
+        for monitor in monitors {
+            if !self.imp().registry.borrow().contains(&monitor) {
+                self.add_output(&monitor);
+            }
+        }
+    }
+
+    pub fn outputs(&self) -> Vec<Output> {
+        self.imp().registry.borrow().outputs().to_vec()
+    }
+
+    /// Finds the output the user is most likely looking at.
+    ///
+    /// Prefers the output with an active (keyboard focused) window, then the one under the pointer,
+    /// and finally falls back to the first output.
+    pub fn focused_output(&self) -> Option<Output> {
+        let registry = self.imp().registry.borrow();
+
+        if let Some(output) = registry
+            .outputs()
+            .iter()
+            .find(|output| output.windows().any(|window| window.is_active()))
+        {
+            return Some(output.clone());
+        }
+
+        let pointer_monitor = gdk::Display::default().and_then(|display| {
+            let (surface, _x, _y) = display.default_seat()?.pointer()?.surface_at_position();
+            display.monitor_at_surface(&surface?)
+        });
+
+        if let Some(output) = pointer_monitor.and_then(|monitor| registry.get(&monitor)) {
+            return Some(output.clone());
+        }
+
+        registry.outputs().first().cloned()
+    }
+
+    pub fn outputs_for(&self, target: &Target) -> Result<Vec<Output>, ipc::Error> {
+        match target {
+            Target::All => Ok(self.outputs()),
+            Target::Focused => Ok(self.focused_output().into_iter().collect()),
+            Target::Connector(connector) => self
+                .imp()
+                .registry
+                .borrow()
+                .by_connector(connector)
+                .map(|output| vec![output.clone()])
+                .ok_or_else(|| ipc::Error::UnknownMonitor(connector.clone())),
+        }
+    }
+
+    /// The single output `target` refers to, the first one if it refers to many
+    pub fn output_for(&self, target: &Target) -> Result<Output, ipc::Error> {
+        self.outputs_for(target)?
+            .into_iter()
+            .next()
+            .ok_or(ipc::Error::NoMonitors)
+    }
+
     pub fn disable_launcher(&self) {
         self.set_launcher_reveal(false);
+
+        let Some(launcher) = self.imp().open_launcher.take() else {
+            return;
+        };
+        launcher.set_reveal(false);
+
         let sender = self.imp().signal_sender.read().unwrap().clone().unwrap();
         glib::spawn_future_local(async move {
             glib::timeout_future(std::time::Duration::from_millis(200)).await;
-            if let Err(e) = sender.send(Message::HideLauncher).await {
-                eprintln!("Launcher Error: {}", e);
+            if let Err(e) = sender.send(Message::HideLauncher { launcher }).await {
+                error!(err = %e, "Could not hide the launcher");
             }
         });
     }
 
-    pub fn enable_launcher(&self) {
-        let window = self.window("Launcher").unwrap();
-        window.set_width_request(500);
-        window.set_default_width(500);
-        if let Err(e) = self.toggle_window("Launcher") {
-            eprintln!("Launcher Error: {}", e);
+    /// Opens the launcher on the targeted monitor, closing it on any other one
+    pub fn enable_launcher(&self, target: &Target) -> Result<(), ipc::Error> {
+        let launcher = match self.output_for(target)?.launcher() {
+            Ok(launcher) => launcher,
+            // the launcher can be turned off on some monitors, so open it wherever it is instead
+            Err(err) if *target == Target::Focused => self
+                .outputs()
+                .iter()
+                .find_map(|output| output.launcher().ok())
+                .ok_or(err)?,
+            Err(err) => return Err(err),
+        };
+
+        if let Some(open) = self.imp().open_launcher.take() {
+            if open != launcher {
+                open.set_reveal(false);
+                open.set_visible(false);
+            }
         }
+
+        launcher.set_width_request(500);
+        launcher.set_default_width(500);
+        launcher.set_visible(true);
+        launcher.set_reveal(true);
+
+        self.imp().open_launcher.replace(Some(launcher));
         self.set_launcher_reveal(true);
+
+        Ok(())
     }
 
-    pub fn handle_message(&self, msg: Message) {
-        match msg {
-            Message::Toggle { component } => match component {
-                Component::Top => self.set_top_reveal(!self.top_reveal()),
-                Component::Right => self.set_right_reveal(!self.right_reveal()),
-                Component::Launcher => {
-                    if self.launcher_reveal() {
-                        self.disable_launcher();
-                    } else {
-                        self.enable_launcher();
+    /// Starts streaming events to `conn`. Only the given event names are sent, or all if empty.
+    pub fn subscribe(&self, conn: &gio::SocketConnection, events: Vec<String>) {
+        let subscriber = events::Subscriber::new(conn, events);
+        self.imp().subscribers.borrow_mut().push(subscriber);
+    }
+
+    pub fn emit(&self, event: Event) {
+        self.imp()
+            .subscribers
+            .borrow_mut()
+            .retain(|subscriber| subscriber.send(&event));
+    }
+
+    /// Runs `command`, returning the reply to send back.
+    ///
+    /// Returns None if the command took over the connection, e.g. to stream events.
+    pub fn handle_command(&self, command: Command, conn: &gio::SocketConnection) -> Option<Reply> {
+        Some(match command {
+            Command::Messages(messages) => {
+                for message in messages {
+                    if let Err(err) = self.handle_message(message) {
+                        return Some(Reply::from(err));
                     }
                 }
+
+                Reply::ok()
+            }
+            Command::Status { target } => match self.outputs_for(&target) {
+                Ok(outputs) => Reply::Ok(Some(status::status(self, outputs.first()))),
+                Err(err) => Reply::from(err),
             },
-            Message::On { component } => match component {
-                Component::Top => self.set_top_reveal(true),
-                Component::Right => self.set_right_reveal(true),
-                Component::Launcher => self.enable_launcher(),
+            Command::Get { query, target } => match self.query(query, &target) {
+                Ok(value) => Reply::Ok(Some(value)),
+                Err(err) => Reply::from(err),
             },
-            Message::Off { component } => match component {
-                Component::Top => self.set_top_reveal(false),
-                Component::Right => self.set_right_reveal(false),
-                Component::Launcher => self.disable_launcher(),
+            Command::Subscribe { events } => {
+                self.subscribe(conn, events);
+                return None;
+            }
+            Command::ReloadCss => match self.reload_css() {
+                Ok(()) => Reply::ok(),
+                Err(err) => Reply::from(err),
             },
-            Message::HideLauncher => {
-                self.set_launcher_reveal(false);
-                self.toggle_window("Launcher").unwrap();
+            Command::LogLevel { level } => {
+                if let Some(level) = level {
+                    if let Err(err) = logging::set_level(&level) {
+                        return Some(Reply::from(ipc::Error::InvalidLogLevel(err)));
+                    }
+                }
+
+                Reply::Ok(Some(serde_json::json!({ "level": logging::level() })))
+            }
+        })
+    }
+
+    fn query(&self, query: Query, target: &Target) -> Result<serde_json::Value, ipc::Error> {
+        // most state lives on a single monitor
+        let output = || self.output_for(target);
+
+        Ok(match query {
+            Query::Launcher => status::launcher(self),
+            Query::Monitors => status::monitors(self),
+            Query::Player => status::player(&output()?.right()?),
+            Query::Notifications => status::notifications(&output()?.notifications()?),
+            Query::Weather => status::weather(&output()?.top()?),
+            Query::Services => status::services(self),
+        })
+    }
+
+    pub fn handle_message(&self, msg: Message) -> Result<(), ipc::Error> {
+        match msg {
+            Message::Toggle {
+                component: Component::Launcher,
+                target,
+            } => {
+                if self.launcher_reveal() {
+                    self.disable_launcher();
+                } else {
+                    self.enable_launcher(&target)?;
+                }
+            }
+            Message::On {
+                component: Component::Launcher,
+                target,
+            } => self.enable_launcher(&target)?,
+            Message::Off {
+                component: Component::Launcher,
+                ..
+            } => self.disable_launcher(),
+            Message::Toggle { component, target } => {
+                self.update_reveal(component, &target, |reveal| !reveal)?
+            }
+            Message::On { component, target } => {
+                self.update_reveal(component, &target, |_| true)?
+            }
+            Message::Off { component, target } => {
+                self.update_reveal(component, &target, |_| false)?
+            }
+            Message::HideLauncher { launcher } => {
+                // it may have been opened again while animating out
+                if !launcher.reveal() {
+                    launcher.set_visible(false);
+                }
             }
         }
+
+        Ok(())
+    }
+
+    fn update_reveal(
+        &self,
+        component: Component,
+        target: &Target,
+        reveal: impl Fn(bool) -> bool,
+    ) -> Result<(), ipc::Error> {
+        for output in self.outputs_for(target)? {
+            let window = output.window(component)?;
+            window.set_property("reveal", reveal(window.property::<bool>("reveal")));
+        }
+
+        Ok(())
     }
 }
 
@@ -119,6 +766,19 @@ impl AppBuilder {
         }
     }
 
+    /// Reads settings from a named profile instead of the default ones
+    pub fn settings_profile(self, settings_profile: Option<String>) -> Self {
+        Self {
+            builder: self.builder.property("settings-profile", settings_profile),
+        }
+    }
+
+    pub fn flags(self, flags: gio::ApplicationFlags) -> Self {
+        Self {
+            builder: self.builder.property("flags", flags),
+        }
+    }
+
     pub fn resource_base_path(self, resource_base_path: impl Into<glib::GString>) -> Self {
         Self {
             builder: self
@@ -146,7 +806,9 @@ impl AppBuilder {
             app,
             async move {
                 while let Some(msg) = signal_receiver.recv().await {
-                    app.handle_message(msg);
+                    if let Err(e) = app.handle_message(msg) {
+                        warn!(err = %e, "Could not handle message");
+                    }
                 }
             }
         ));
@@ -156,23 +818,34 @@ impl AppBuilder {
 }
 
 mod imp {
-    use std::sync::RwLock;
+    use std::{cell::RefCell, sync::RwLock};
 
     use astal_io::{functions::write_sock, subclass::prelude::AstalIOApplicationImpl};
     use glib::Properties;
 
     use super::*;
+    use crate::ipc;
 
     #[derive(Properties, Default, Debug)]
     #[properties(wrapper_type = super::App)]
     pub struct App {
-        #[property(get, set)]
-        pub top_reveal: RwLock<bool>,
-        #[property(get, set)]
-        pub right_reveal: RwLock<bool>,
         #[property(get, set)]
         pub launcher_reveal: RwLock<bool>,
+        #[property(get, construct_only)]
+        pub settings_profile: RefCell<Option<String>>,
         pub signal_sender: RwLock<Option<Sender<Message>>>,
+        pub registry: RefCell<Registry>,
+        /// The launcher that is currently open, if any
+        pub open_launcher: RefCell<Option<Launcher>>,
+        /// What was shown on each monitor, by connector, as of the last save or disconnect
+        pub session: RefCell<Session>,
+        /// The timeout that will save the session, if one is running
+        pub pending_save: RefCell<Option<glib::SourceId>>,
+        pub subscribers: RefCell<Vec<events::Subscriber>>,
+        pub services: RefCell<Services>,
+        pub config: RefCell<Rc<UserConfig>>,
+        /// Monitors for the config file and stylesheets, kept alive for as long as the app is
+        pub file_monitors: RefCell<Vec<gio::FileMonitor>>,
     }
 
     #[glib::object_subclass]
@@ -188,78 +861,36 @@ mod imp {
         fn request(&self, msg: &str, conn: &gio::SocketConnection) -> Result<(), glib::Error> {
             let obj = self.obj();
 
-            let messages = match msg {
-                "launcher" => {
-                    vec![Message::Toggle {
-                        component: Component::Launcher,
-                    }]
-                }
-                "toggle top" => {
-                    vec![Message::Toggle {
-                        component: Component::Top,
-                    }]
-                }
-                "toggle right" => {
-                    vec![Message::Toggle {
-                        component: Component::Right,
-                    }]
-                }
-                "toggle all" => {
-                    vec![
-                        Message::Toggle {
-                            component: Component::Top,
-                        },
-                        Message::Toggle {
-                            component: Component::Right,
-                        },
-                    ]
-                }
-                "on all" => {
-                    vec![
-                        Message::On {
-                            component: Component::Top,
-                        },
-                        Message::On {
-                            component: Component::Right,
-                        },
-                    ]
-                }
-                "off all" => {
-                    vec![
-                        Message::Off {
-                            component: Component::Top,
-                        },
-                        Message::Off {
-                            component: Component::Right,
-                        },
-                    ]
-                }
-                msg => {
-                    write_sock(conn, msg, |res| {
-                        if let Err(err) = res {
-                            eprintln!("Error: {}", err);
-                        }
-                    });
-                    return Ok(());
-                }
+            let reply = match ipc::parse(msg) {
+                Ok(command) => obj.handle_command(command, conn),
+                Err(err) => Some(Reply::from(err)),
             };
 
-            for message in messages {
-                obj.handle_message(message);
-            }
+            let Some(reply) = reply else {
+                return Ok(());
+            };
+
+            write_sock(conn, &reply.to_json(), |res| {
+                if let Err(err) = res {
+                    error!(%err, "Could not write reply");
+                }
+            });
 
             Ok(())
         }
     }
     impl GtkApplicationImpl for App {}
-    impl ApplicationImpl for App {}
+    impl ApplicationImpl for App {
+        fn shutdown(&self) {
+            self.obj().save_session();
+            self.parent_shutdown();
+        }
+    }
 
     #[glib::derived_properties]
     impl ObjectImpl for App {
         fn constructed(&self) {
             self.parent_constructed();
-            self.obj().set_top_reveal(false);
-            self.obj().set_right_reveal(false);
             self.obj().set_launcher_reveal(false);
         }
     }
@@ -0,0 +1,166 @@
+//! Auto-hiding panels, revealed by hovering their edge.
+//!
+//! A hidden panel's window shrinks down to a thin strip along its edge for the pointer to hit.
+//! Touching it reveals the panel, which hides again a little while after the pointer leaves.
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gtk::{glib, prelude::*};
+
+use crate::user_config::Edge;
+
+/// How thick the strip along a hidden panel's edge is, in pixels
+const STRIP_SIZE: i32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct AutoHide {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    window: glib::WeakRef<gtk::Window>,
+    /// Weak, as the controller's handlers hold on to this
+    hover: glib::WeakRef<gtk::EventControllerMotion>,
+    hide_delay: Duration,
+    peek_duration: Duration,
+    /// The timeout that will hide the panel, if one is running
+    pending: RefCell<Option<glib::SourceId>>,
+}
+
+impl AutoHide {
+    /// Makes `window`, docked to `edge`, auto-hide. It must have a `reveal` property.
+    pub fn attach(
+        window: &impl IsA<gtk::Window>,
+        edge: Edge,
+        hide_delay: Duration,
+        peek_duration: Duration,
+    ) -> Self {
+        let window = window.upcast_ref::<gtk::Window>();
+        add_strip(window, edge);
+
+        let hover = gtk::EventControllerMotion::new();
+        window.add_controller(hover.clone());
+
+        let auto_hide = Self {
+            inner: Rc::new(Inner {
+                window: window.downgrade(),
+                hover: hover.downgrade(),
+                hide_delay,
+                peek_duration,
+                pending: RefCell::new(None),
+            }),
+        };
+
+        hover.connect_enter(glib::clone!(
+            #[strong]
+            auto_hide,
+            move |_hover, _x, _y| auto_hide.reveal()
+        ));
+
+        hover.connect_leave(glib::clone!(
+            #[strong]
+            auto_hide,
+            move |_hover| auto_hide.hide_after(auto_hide.inner.hide_delay)
+        ));
+
+        // menus and entries keep the window active, so wait until they're done with
+        window.connect_is_active_notify(glib::clone!(
+            #[strong]
+            auto_hide,
+            move |window| {
+                if !window.is_active() {
+                    auto_hide.hide_after(auto_hide.inner.hide_delay);
+                }
+            }
+        ));
+
+        window.set_property("reveal", false);
+        auto_hide
+    }
+
+    /// Briefly shows the panel, or for as long as the pointer is over it
+    pub fn peek(&self) {
+        if self.inner.peek_duration.is_zero() {
+            return;
+        }
+
+        self.reveal();
+        self.hide_after(self.inner.peek_duration);
+    }
+
+    fn reveal(&self) {
+        self.cancel();
+
+        if let Some(window) = self.inner.window.upgrade() {
+            window.set_property("reveal", true);
+        }
+    }
+
+    fn hide_after(&self, delay: Duration) {
+        self.cancel();
+
+        let source = glib::timeout_add_local_once(
+            delay,
+            glib::clone!(
+                #[strong(rename_to = auto_hide)]
+                self,
+                move || {
+                    auto_hide.inner.pending.take();
+
+                    let Some(window) = auto_hide.inner.window.upgrade() else {
+                        return;
+                    };
+
+                    let hovered = auto_hide
+                        .inner
+                        .hover
+                        .upgrade()
+                        .is_some_and(|hover| hover.contains_pointer());
+
+                    if !hovered && !window.is_active() {
+                        window.set_property("reveal", false);
+                    }
+                }
+            ),
+        );
+
+        self.inner.pending.replace(Some(source));
+    }
+
+    fn cancel(&self) {
+        if let Some(source) = self.inner.pending.take() {
+            source.remove();
+        }
+    }
+}
+
+/// Puts a strip between `window`'s content and `edge`. A hidden panel's content can collapse to
+/// nothing, so this is what keeps something there for the pointer to touch.
+fn add_strip(window: &gtk::Window, edge: Edge) {
+    let strip = gtk::Box::builder().css_classes(["auto-hide-strip"]).build();
+    let container = if edge.is_horizontal() {
+        strip.set_height_request(STRIP_SIZE);
+        gtk::Box::new(gtk::Orientation::Vertical, 0)
+    } else {
+        strip.set_width_request(STRIP_SIZE);
+        gtk::Box::new(gtk::Orientation::Horizontal, 0)
+    };
+
+    let content = window.child();
+    window.set_child(None::<&gtk::Widget>);
+
+    // the panel slides out from behind the strip, so it goes next to the screen edge
+    let leading = matches!(edge, Edge::Top | Edge::Left);
+    if leading {
+        container.append(&strip);
+    }
+    if let Some(content) = &content {
+        container.append(content);
+    }
+    if !leading {
+        container.append(&strip);
+    }
+
+    window.set_child(Some(&container));
+}
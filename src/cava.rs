@@ -29,6 +29,7 @@ mod imp {
     use astal_cava::prelude::*;
     use glib::Properties;
     use gtk::gsk::{FillRule, PathBuilder};
+    use tracing::warn;
 
     use super::*;
 
@@ -53,7 +54,7 @@ mod imp {
 
             let obj = self.obj();
             let Some(cava) = obj.cava() else {
-                eprintln!("Cava is not set");
+                warn!("Cava is not set");
                 return;
             };
 
@@ -98,15 +99,17 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
             let obj = self.obj();
+            obj.set_css_classes(&["cava"]);
 
-            obj.set_cava(Some(
-                astal_cava::functions::default().expect("Expected Cava to exist"),
-            ));
+            // the reason is recorded by `App::detect_services`
+            let Some(cava) = astal_cava::functions::default() else {
+                obj.set_visible(false);
+                return;
+            };
 
-            let cava = obj.cava().unwrap();
+            obj.set_cava(Some(&cava));
 
-            cava.set_bars(100);
-            cava.set_framerate(90);
+            // bars and framerate come from the config, see `App::watch_config`
             cava.set_noise_reduction(0.22);
             cava.set_stereo(true);
 
@@ -117,8 +120,6 @@ mod imp {
                     obj.queue_draw();
                 }
             ));
-
-            obj.set_css_classes(&["cava"]);
         }
     }
 }
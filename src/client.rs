@@ -0,0 +1,188 @@
+//! Client mode: talks to a running shell over its socket instead of starting a new one.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use clap::Subcommand;
+use gtk::glib;
+use serde_json::Value;
+
+/// Marks the end of a request or reply on the astal socket
+const END_OF_MESSAGE: u8 = 0x04;
+
+/// The default instance name used by astal
+pub const DEFAULT_INSTANCE: &str = "astal";
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Send a raw request, e.g. `commashell msg toggle right --monitor DP-2`
+    Msg {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        request: Vec<String>,
+    },
+    /// Show everything the shell is doing
+    Status {
+        /// Monitor to read per-monitor state from (a connector, `focused` or `all`)
+        #[arg(long)]
+        monitor: Option<String>,
+    },
+    /// Show a single piece of state (launcher, monitors, player, notifications, weather or services)
+    Get {
+        query: String,
+        /// Monitor to read per-monitor state from (a connector, `focused` or `all`)
+        #[arg(long)]
+        monitor: Option<String>,
+    },
+    /// Print events as they happen, one JSON object per line
+    Subscribe {
+        /// Only print these events
+        events: Vec<String>,
+    },
+}
+
+impl Command {
+    fn request(&self) -> String {
+        let with_monitor = |request: String, monitor: &Option<String>| match monitor {
+            Some(monitor) => format!("{request} --monitor {monitor}"),
+            None => request,
+        };
+
+        match self {
+            Command::Msg { request } => request.join(" "),
+            Command::Status { monitor } => with_monitor("status".to_string(), monitor),
+            Command::Get { query, monitor } => with_monitor(format!("get {query}"), monitor),
+            Command::Subscribe { events } => std::iter::once("subscribe".to_string())
+                .chain(events.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// Exit codes, on top of clap's own 2 for bad usage
+pub mod exit {
+    /// The shell replied with an error
+    pub const ERROR_REPLY: i32 = 1;
+    /// The shell isn't running or the connection broke
+    pub const UNAVAILABLE: i32 = 3;
+}
+
+pub fn socket_path(instance: &str) -> PathBuf {
+    glib::user_runtime_dir()
+        .join("astal")
+        .join(format!("{instance}.sock"))
+}
+
+/// Whether a shell is listening on the instance's socket
+pub fn is_running(instance: &str) -> bool {
+    UnixStream::connect(socket_path(instance)).is_ok()
+}
+
+/// Sends `command` to the instance and prints the reply.
+///
+/// With `json` the reply is printed as-is, otherwise only its data is, pretty printed.
+pub fn run(instance: &str, command: Command, json: bool) -> glib::ExitCode {
+    let path = socket_path(instance);
+    let stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!(
+                "commashell: could not connect to instance {instance} at {}: {err}",
+                path.display()
+            );
+            return glib::ExitCode::from(exit::UNAVAILABLE);
+        }
+    };
+
+    let streaming = matches!(command, Command::Subscribe { .. });
+    match send(stream, &command.request(), streaming, json) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("commashell: {err}");
+            glib::ExitCode::from(exit::UNAVAILABLE)
+        }
+    }
+}
+
+fn send(
+    mut stream: UnixStream,
+    request: &str,
+    streaming: bool,
+    json: bool,
+) -> io::Result<glib::ExitCode> {
+    stream.write_all(request.as_bytes())?;
+    stream.write_all(&[END_OF_MESSAGE])?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+
+    // a stream starts with a reply line, anything else ends with the end of message marker
+    let delimiter = if streaming { b'\n' } else { END_OF_MESSAGE };
+    let Some(reply) = read_message(&mut reader, delimiter)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the shell closed the connection without replying",
+        ));
+    };
+
+    if !print_reply(&reply, json) {
+        return Ok(glib::ExitCode::from(exit::ERROR_REPLY));
+    }
+
+    if !streaming {
+        return Ok(glib::ExitCode::SUCCESS);
+    }
+
+    let mut stdout = io::stdout();
+    while let Some(event) = read_message(&mut reader, b'\n')? {
+        writeln!(stdout, "{event}")?;
+        stdout.flush()?;
+    }
+
+    Ok(glib::ExitCode::SUCCESS)
+}
+
+/// Reads up to `delimiter`, returning None once the connection is closed
+fn read_message(reader: &mut impl BufRead, delimiter: u8) -> io::Result<Option<String>> {
+    let mut buffer = Vec::new();
+    if reader.read_until(delimiter, &mut buffer)? == 0 {
+        return Ok(None);
+    }
+
+    let message = String::from_utf8_lossy(&buffer);
+    let message = message.trim_end_matches(['\n', END_OF_MESSAGE as char]);
+    Ok(Some(message.to_string()))
+}
+
+/// Prints a reply, returning whether it was successful
+fn print_reply(reply: &str, json: bool) -> bool {
+    let parsed = serde_json::from_str::<Value>(reply).ok();
+    let ok = parsed
+        .as_ref()
+        .and_then(|reply| reply["ok"].as_bool())
+        .unwrap_or(false);
+
+    if json {
+        println!("{reply}");
+    } else if let Some(reply) = &parsed {
+        if !ok {
+            let message = reply["error"]["message"]
+                .as_str()
+                .unwrap_or("unknown error");
+            eprintln!("commashell: {message}");
+        } else if let Some(data) = reply.get("data") {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(data).unwrap_or_else(|_| data.to_string())
+            );
+        }
+    } else {
+        // not one of our replies, e.g. from an older version of the shell
+        println!("{reply}");
+    }
+
+    ok
+}
@@ -0,0 +1,330 @@
+//! Events streamed to clients that sent `subscribe` over the socket.
+//!
+//! Each event is written as a single line of JSON, e.g.
+//! `{"event":"reveal","component":"right","monitor":"DP-2","revealed":true}`.
+
+use std::cell::RefCell;
+
+use astal_mpris::{prelude::*, Player};
+use astal_notifd::prelude::*;
+use gtk::{
+    gio,
+    glib::{self, SignalHandlerId},
+    prelude::*,
+};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, warn};
+
+use crate::{
+    app::{App, Component},
+    ipc::Reply,
+    registry::Output,
+    right::Right,
+    status,
+};
+
+/// How many events can be queued for a subscriber before it's considered stuck and dropped
+const SUBSCRIBER_BACKLOG: usize = 64;
+
+#[derive(Debug)]
+pub enum Event {
+    /// A component was shown or hidden. `monitor` is null for the launcher.
+    Reveal {
+        component: Component,
+        monitor: Option<String>,
+        revealed: bool,
+    },
+    /// The selected player of a music panel changed
+    Player {
+        monitor: Option<String>,
+        player: Value,
+    },
+    /// The selected player started playing something else
+    Track {
+        monitor: Option<String>,
+        player: Value,
+    },
+    Notified {
+        notification: Value,
+        replaced: bool,
+    },
+    Resolved {
+        id: u32,
+        reason: &'static str,
+    },
+    Weather {
+        monitor: Option<String>,
+        weather: Value,
+    },
+    /// A monitor was plugged in or out
+    Monitor {
+        connector: Option<String>,
+        connected: bool,
+    },
+}
+
+impl Event {
+    /// Every event name, for filtering subscriptions
+    pub const NAMES: [&'static str; 7] = [
+        "reveal", "player", "track", "notified", "resolved", "weather", "monitor",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Reveal { .. } => "reveal",
+            Event::Player { .. } => "player",
+            Event::Track { .. } => "track",
+            Event::Notified { .. } => "notified",
+            Event::Resolved { .. } => "resolved",
+            Event::Weather { .. } => "weather",
+            Event::Monitor { .. } => "monitor",
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut value = match self {
+            Event::Reveal {
+                component,
+                monitor,
+                revealed,
+            } => json!({
+                "component": component.name(),
+                "monitor": monitor,
+                "revealed": revealed,
+            }),
+            Event::Player { monitor, player } | Event::Track { monitor, player } => json!({
+                "monitor": monitor,
+                "player": player,
+            }),
+            Event::Notified {
+                notification,
+                replaced,
+            } => json!({
+                "notification": notification,
+                "replaced": replaced,
+            }),
+            Event::Resolved { id, reason } => json!({
+                "id": id,
+                "reason": reason,
+            }),
+            Event::Weather { monitor, weather } => json!({
+                "monitor": monitor,
+                "weather": weather,
+            }),
+            Event::Monitor {
+                connector,
+                connected,
+            } => json!({
+                "connector": connector,
+                "connected": connected,
+            }),
+        };
+
+        value["event"] = self.name().into();
+        value.to_string()
+    }
+}
+
+/// A client connection receiving events
+#[derive(Debug)]
+pub struct Subscriber {
+    sender: mpsc::Sender<String>,
+    /// Event names to send, or every event if empty
+    filter: Vec<String>,
+}
+
+impl Subscriber {
+    /// Starts streaming to `conn`, beginning with an `{"ok":true}` line
+    pub fn new(conn: &gio::SocketConnection, filter: Vec<String>) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<String>(SUBSCRIBER_BACKLOG);
+        let output = conn.output_stream();
+        // keep the connection alive for as long as we're writing to it
+        let conn = conn.clone();
+
+        glib::spawn_future_local(async move {
+            let _conn = conn;
+
+            while let Some(line) = receiver.recv().await {
+                match output
+                    .write_all_future(line + "\n", glib::Priority::DEFAULT)
+                    .await
+                {
+                    Ok((_, _, None)) => {}
+                    Ok((_, _, Some(err))) | Err((_, err)) => {
+                        debug!(%err, "Subscriber disconnected");
+                        break;
+                    }
+                }
+            }
+        });
+
+        let subscriber = Self { sender, filter };
+        subscriber.send_line(Reply::ok().to_json());
+        subscriber
+    }
+
+    /// Queues `event` if the subscriber wants it.
+    ///
+    /// Returns false once the subscriber has disconnected or fallen too far behind.
+    pub fn send(&self, event: &Event) -> bool {
+        if !self.filter.is_empty() && !self.filter.iter().any(|name| name == event.name()) {
+            return !self.sender.is_closed();
+        }
+
+        self.send_line(event.to_json())
+    }
+
+    fn send_line(&self, line: String) -> bool {
+        match self.sender.try_send(line) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                warn!("Dropping subscriber that stopped reading events");
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Emits events for state that isn't tied to a monitor
+pub fn watch_app(app: &App) {
+    app.connect_launcher_reveal_notify(|app| {
+        app.emit(Event::Reveal {
+            component: Component::Launcher,
+            monitor: None,
+            revealed: app.launcher_reveal(),
+        });
+    });
+
+    let Some(notifd) = astal_notifd::functions::default() else {
+        return;
+    };
+
+    notifd.connect_notified(glib::clone!(
+        #[weak]
+        app,
+        move |notifd, id, replaced| {
+            let Some(notification) = notifd.notification(id) else {
+                return;
+            };
+
+            app.emit(Event::Notified {
+                notification: status::notification(&notification),
+                replaced,
+            });
+        }
+    ));
+
+    notifd.connect_resolved(glib::clone!(
+        #[weak]
+        app,
+        move |_notifd, id, reason| {
+            app.emit(Event::Resolved {
+                id,
+                reason: match reason {
+                    astal_notifd::ClosedReason::Expired => "expired",
+                    astal_notifd::ClosedReason::DismissedByUser => "dismissed",
+                    astal_notifd::ClosedReason::Closed => "closed",
+                    _ => "undefined",
+                },
+            });
+        }
+    ));
+}
+
+/// Emits events for the windows of a single monitor
+pub fn watch_output(app: &App, output: &Output) {
+    let monitor = output.connector().map(|c| c.to_string());
+
+    for component in [Component::Top, Component::Right] {
+        let Ok(window) = output.window(component) else {
+            continue;
+        };
+
+        window.connect_notify_local(
+            Some("reveal"),
+            glib::clone!(
+                #[weak]
+                app,
+                #[strong]
+                monitor,
+                move |window, _| {
+                    app.emit(Event::Reveal {
+                        component,
+                        monitor: monitor.clone(),
+                        revealed: window.property("reveal"),
+                    });
+                }
+            ),
+        );
+    }
+
+    if let Ok(right) = output.right() {
+        // the title handler of the currently selected player, swapped out when the player changes
+        let track_handler = RefCell::new(None::<(Player, SignalHandlerId)>);
+        watch_track(app, &right, &monitor, &track_handler);
+
+        right.connect_player_notify(glib::clone!(
+            #[weak]
+            app,
+            #[strong]
+            monitor,
+            move |right| {
+                app.emit(Event::Player {
+                    monitor: monitor.clone(),
+                    player: status::player(right),
+                });
+
+                watch_track(&app, right, &monitor, &track_handler);
+            }
+        ));
+    }
+
+    if let Ok(top) = output.top() {
+        top.connect_weather_updated_notify(glib::clone!(
+            #[weak]
+            app,
+            #[strong]
+            monitor,
+            move |top| {
+                app.emit(Event::Weather {
+                    monitor: monitor.clone(),
+                    weather: status::weather(top),
+                });
+            }
+        ));
+    }
+}
+
+fn watch_track(
+    app: &App,
+    right: &Right,
+    monitor: &Option<String>,
+    track_handler: &RefCell<Option<(Player, SignalHandlerId)>>,
+) {
+    if let Some((player, handler)) = track_handler.take() {
+        player.disconnect(handler);
+    }
+
+    let Some(player) = right.player() else {
+        return;
+    };
+
+    let handler = player.connect_title_notify(glib::clone!(
+        #[weak]
+        app,
+        #[weak]
+        right,
+        #[strong]
+        monitor,
+        move |_player| {
+            app.emit(Event::Track {
+                monitor: monitor.clone(),
+                player: status::player(&right),
+            });
+        }
+    ));
+
+    track_handler.replace(Some((player, handler)));
+}
@@ -0,0 +1,651 @@
+//! Parsing and replies for requests sent over the astal socket.
+//!
+//! A request is a single line in the form `<verb> [component] [--option value]...`,
+//! for example `toggle right --monitor DP-2` or `get player`. Every request gets a JSON reply,
+//! either `{"ok":true}`, `{"ok":true,"data":...}` for queries, or
+//! `{"ok":false,"error":{"kind":"...","message":"..."}}`.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::{
+    app::{Component, Message, Target},
+    events::Event,
+};
+
+/// A fully parsed request
+#[derive(Debug)]
+pub enum Command {
+    /// Messages to run in order on the app
+    Messages(Vec<Message>),
+    /// Everything [`Query`] can return, at once
+    Status { target: Target },
+    /// A single piece of state
+    Get { query: Query, target: Target },
+    /// Keep the connection open and stream events, optionally only the named ones
+    Subscribe { events: Vec<String> },
+    /// Re-apply the built-in and user stylesheets from disk
+    ReloadCss,
+    /// Change the log level if one is given, replying with the current one
+    LogLevel { level: Option<String> },
+}
+
+/// Read-only state that can be asked for with `get`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    Launcher,
+    Monitors,
+    Player,
+    Notifications,
+    Weather,
+    Services,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Nothing was sent
+    Empty,
+    /// The verb is not a known command
+    UnknownCommand(String),
+    /// The component is not a known component
+    UnknownComponent(String),
+    /// A required positional argument was not given
+    MissingArgument(&'static str),
+    /// More positional arguments were given than the command accepts
+    UnexpectedArgument(String),
+    /// An option the command does not understand
+    UnknownOption(String),
+    /// An option was given without a value
+    MissingOptionValue(String),
+    /// No monitor with the given connector exists
+    UnknownMonitor(String),
+    /// There are no monitors to answer the request with
+    NoMonitors,
+    /// The component has no window on the monitor
+    MissingWindow {
+        component: Component,
+        monitor: Option<String>,
+    },
+    /// The thing asked for with `get` doesn't exist
+    UnknownQuery(String),
+    /// The event given to `subscribe` doesn't exist
+    UnknownEvent(String),
+    /// A stylesheet couldn't be read
+    Stylesheet(String),
+    /// The directives given to `log-level` couldn't be parsed
+    InvalidLogLevel(String),
+}
+
+impl Error {
+    /// Stable identifier for the error, for scripts to match on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Empty => "empty-request",
+            Error::UnknownCommand(_) => "unknown-command",
+            Error::UnknownComponent(_) => "unknown-component",
+            Error::MissingArgument(_) => "missing-argument",
+            Error::UnexpectedArgument(_) => "unexpected-argument",
+            Error::UnknownOption(_) => "unknown-option",
+            Error::MissingOptionValue(_) => "missing-option-value",
+            Error::UnknownMonitor(_) => "unknown-monitor",
+            Error::NoMonitors => "no-monitors",
+            Error::MissingWindow { .. } => "missing-window",
+            Error::UnknownQuery(_) => "unknown-query",
+            Error::UnknownEvent(_) => "unknown-event",
+            Error::Stylesheet(_) => "stylesheet",
+            Error::InvalidLogLevel(_) => "invalid-log-level",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty request"),
+            Error::UnknownCommand(command) => write!(f, "unknown command: {command}"),
+            Error::UnknownComponent(component) => write!(
+                f,
+                "unknown component: {component} (expected top, right, launcher or all)"
+            ),
+            Error::MissingArgument(argument) => write!(f, "missing argument: {argument}"),
+            Error::UnexpectedArgument(argument) => write!(f, "unexpected argument: {argument}"),
+            Error::UnknownOption(option) => write!(f, "unknown option: --{option}"),
+            Error::MissingOptionValue(option) => write!(f, "missing value for --{option}"),
+            Error::UnknownMonitor(connector) => write!(f, "no monitor with connector: {connector}"),
+            Error::NoMonitors => write!(f, "no monitors are connected"),
+            Error::MissingWindow { component, monitor } => write!(
+                f,
+                "{} has no window on monitor {}",
+                component.name(),
+                monitor.as_deref().unwrap_or("(unknown)")
+            ),
+            Error::UnknownEvent(event) => write!(
+                f,
+                "unknown event: {event} (expected one of {})",
+                Event::NAMES.join(", ")
+            ),
+            Error::Stylesheet(err) => write!(f, "could not load stylesheet: {err}"),
+            Error::InvalidLogLevel(err) => write!(f, "invalid log level: {err}"),
+            Error::UnknownQuery(query) => write!(
+                f,
+                "unknown query: {query} (expected launcher, monitors, player, notifications, weather or services)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The reply written back to the socket for every request
+#[derive(Debug)]
+pub enum Reply {
+    Ok(Option<Value>),
+    Err(Error),
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply::Ok(None)
+    }
+
+    pub fn to_json(&self) -> String {
+        let value = match self {
+            Reply::Ok(None) => json!({ "ok": true }),
+            Reply::Ok(Some(data)) => json!({ "ok": true, "data": data }),
+            Reply::Err(error) => json!({
+                "ok": false,
+                "error": {
+                    "kind": error.kind(),
+                    "message": error.to_string(),
+                },
+            }),
+        };
+
+        value.to_string()
+    }
+}
+
+impl From<Error> for Reply {
+    fn from(error: Error) -> Self {
+        Reply::Err(error)
+    }
+}
+
+/// A request split into positional arguments and `--option value` pairs
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn split(tokens: impl Iterator<Item = &'a str>) -> Result<Self, Error> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut tokens = tokens.peekable();
+
+        while let Some(token) = tokens.next() {
+            let Some(option) = token.strip_prefix("--") else {
+                positional.push(token);
+                continue;
+            };
+
+            // support both `--option value` and `--option=value`
+            if let Some((name, value)) = option.split_once('=') {
+                options.push((name, value));
+                continue;
+            }
+
+            match tokens.next_if(|next| !next.starts_with("--")) {
+                Some(value) => options.push((option, value)),
+                None => return Err(Error::MissingOptionValue(option.to_string())),
+            }
+        }
+
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    /// Errors if any positional arguments are left after the first `count`
+    fn expect_at_most(&self, count: usize) -> Result<(), Error> {
+        match self.positional.get(count) {
+            Some(extra) => Err(Error::UnexpectedArgument(extra.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// The value of the last `--name` option given
+    fn option(&self, name: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| *option == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Errors if any option outside of `known` was given
+    fn expect_options(&self, known: &[&str]) -> Result<(), Error> {
+        match self.options.iter().find(|(name, _)| !known.contains(name)) {
+            Some((name, _)) => Err(Error::UnknownOption(name.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn parse(request: &str) -> Result<Command, Error> {
+    let mut tokens = request.split_whitespace();
+    let verb = tokens.next().ok_or(Error::Empty)?;
+    let args = Args::split(tokens)?;
+
+    match verb {
+        "toggle" | "on" | "off" => {
+            args.expect_at_most(1)?;
+
+            let component = args
+                .positional
+                .first()
+                .ok_or(Error::MissingArgument("component"))?;
+
+            let components = parse_components(component)?;
+            args.expect_options(&["monitor"])?;
+
+            let target = args.option("monitor").map(parse_target);
+            let messages = components
+                .into_iter()
+                .map(|component| {
+                    // the launcher only opens on one monitor, so it follows focus by default
+                    let target = target.clone().unwrap_or(match component {
+                        Component::Launcher => Target::Focused,
+                        _ => Target::All,
+                    });
+
+                    match verb {
+                        "toggle" => Message::Toggle { component, target },
+                        "on" => Message::On { component, target },
+                        _ => Message::Off { component, target },
+                    }
+                })
+                .collect();
+
+            Ok(Command::Messages(messages))
+        }
+        "status" => {
+            args.expect_at_most(0)?;
+            args.expect_options(&["monitor"])?;
+
+            Ok(Command::Status {
+                target: focused_target(&args),
+            })
+        }
+        "get" => {
+            args.expect_at_most(1)?;
+            args.expect_options(&["monitor"])?;
+
+            let query = args
+                .positional
+                .first()
+                .ok_or(Error::MissingArgument("query"))?;
+
+            Ok(Command::Get {
+                query: parse_query(query)?,
+                target: focused_target(&args),
+            })
+        }
+        "subscribe" => {
+            args.expect_options(&[])?;
+
+            if let Some(unknown) = args
+                .positional
+                .iter()
+                .find(|event| !Event::NAMES.contains(event))
+            {
+                return Err(Error::UnknownEvent(unknown.to_string()));
+            }
+
+            Ok(Command::Subscribe {
+                events: args.positional.iter().map(|e| e.to_string()).collect(),
+            })
+        }
+        "reload-css" => {
+            args.expect_at_most(0)?;
+            args.expect_options(&[])?;
+
+            Ok(Command::ReloadCss)
+        }
+        "log-level" => {
+            args.expect_at_most(1)?;
+            args.expect_options(&[])?;
+
+            Ok(Command::LogLevel {
+                level: args.positional.first().map(|level| level.to_string()),
+            })
+        }
+        // kept for existing keybindings, same as `toggle launcher`
+        "launcher" => {
+            args.expect_at_most(0)?;
+            args.expect_options(&["monitor"])?;
+
+            Ok(Command::Messages(vec![Message::Toggle {
+                component: Component::Launcher,
+                target: focused_target(&args),
+            }]))
+        }
+        verb => Err(Error::UnknownCommand(verb.to_string())),
+    }
+}
+
+fn parse_components(component: &str) -> Result<Vec<Component>, Error> {
+    match component {
+        "top" => Ok(vec![Component::Top]),
+        "right" => Ok(vec![Component::Right]),
+        "launcher" => Ok(vec![Component::Launcher]),
+        "all" => Ok(vec![Component::Top, Component::Right]),
+        component => Err(Error::UnknownComponent(component.to_string())),
+    }
+}
+
+fn parse_target(monitor: &str) -> Target {
+    match monitor {
+        "focused" => Target::Focused,
+        "all" => Target::All,
+        connector => Target::Connector(connector.to_string()),
+    }
+}
+
+/// Queries and the launcher use the focused monitor unless told otherwise
+fn focused_target(args: &Args) -> Target {
+    args.option("monitor")
+        .map(parse_target)
+        .unwrap_or(Target::Focused)
+}
+
+fn parse_query(query: &str) -> Result<Query, Error> {
+    match query {
+        "launcher" => Ok(Query::Launcher),
+        "monitors" => Ok(Query::Monitors),
+        "player" => Ok(Query::Player),
+        "notifications" => Ok(Query::Notifications),
+        "weather" => Ok(Query::Weather),
+        "services" => Ok(Query::Services),
+        query => Err(Error::UnknownQuery(query.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The verb, component and target of each message `request` parses to
+    fn messages(request: &str) -> Vec<(&'static str, Component, Target)> {
+        let Ok(Command::Messages(messages)) = parse(request) else {
+            panic!("{request:?} didn't parse to messages");
+        };
+
+        messages
+            .into_iter()
+            .map(|message| match message {
+                Message::Toggle { component, target } => ("toggle", component, target),
+                Message::On { component, target } => ("on", component, target),
+                Message::Off { component, target } => ("off", component, target),
+                Message::HideLauncher { .. } => panic!("{request:?} hid the launcher"),
+            })
+            .collect()
+    }
+
+    /// The error `request` fails with, checking its kind along the way
+    fn error(request: &str, kind: &str) -> Error {
+        let err = parse(request).expect_err(request);
+        assert_eq!(err.kind(), kind, "{request:?} failed with {err:?}");
+        err
+    }
+
+    #[test]
+    fn toggles_on_every_monitor_by_default() {
+        assert_eq!(
+            messages("toggle top"),
+            [("toggle", Component::Top, Target::All)]
+        );
+        assert_eq!(
+            messages("on right"),
+            [("on", Component::Right, Target::All)]
+        );
+        assert_eq!(messages("off top"), [("off", Component::Top, Target::All)]);
+    }
+
+    #[test]
+    fn toggles_the_launcher_on_the_focused_monitor_by_default() {
+        assert_eq!(
+            messages("toggle launcher"),
+            [("toggle", Component::Launcher, Target::Focused)]
+        );
+    }
+
+    #[test]
+    fn expands_all_to_the_panels() {
+        assert_eq!(
+            messages("off all"),
+            [
+                ("off", Component::Top, Target::All),
+                ("off", Component::Right, Target::All),
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_the_monitor_as_a_separate_or_joined_value() {
+        let dp2 = Target::Connector("DP-2".to_string());
+
+        assert_eq!(
+            messages("toggle right --monitor DP-2"),
+            [("toggle", Component::Right, dp2.clone())]
+        );
+        assert_eq!(
+            messages("toggle right --monitor=DP-2"),
+            [("toggle", Component::Right, dp2)]
+        );
+        assert_eq!(
+            messages("on launcher --monitor all"),
+            [("on", Component::Launcher, Target::All)]
+        );
+        assert_eq!(
+            messages("on top --monitor focused"),
+            [("on", Component::Top, Target::Focused)]
+        );
+    }
+
+    #[test]
+    fn uses_the_last_monitor_given() {
+        assert_eq!(
+            messages("on top --monitor DP-1 --monitor=HDMI-A-1"),
+            [(
+                "on",
+                Component::Top,
+                Target::Connector("HDMI-A-1".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn needs_a_value_for_monitor() {
+        let missing = Error::MissingOptionValue("monitor".to_string());
+
+        assert_eq!(
+            error("toggle top --monitor", "missing-option-value"),
+            missing
+        );
+        assert_eq!(
+            error(
+                "toggle top --monitor --monitor DP-2",
+                "missing-option-value"
+            ),
+            missing
+        );
+    }
+
+    #[test]
+    fn parses_the_launcher_alias_as_a_launcher_toggle() {
+        assert_eq!(
+            messages("launcher"),
+            [("toggle", Component::Launcher, Target::Focused)]
+        );
+        assert_eq!(
+            messages("launcher --monitor eDP-1"),
+            [(
+                "toggle",
+                Component::Launcher,
+                Target::Connector("eDP-1".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_status() {
+        assert!(matches!(
+            parse("status"),
+            Ok(Command::Status {
+                target: Target::Focused
+            })
+        ));
+        assert!(matches!(
+            parse("status --monitor all"),
+            Ok(Command::Status {
+                target: Target::All
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_every_query() {
+        for (name, query) in [
+            ("launcher", Query::Launcher),
+            ("monitors", Query::Monitors),
+            ("player", Query::Player),
+            ("notifications", Query::Notifications),
+            ("weather", Query::Weather),
+            ("services", Query::Services),
+        ] {
+            let parsed = parse(&format!("get {name}"));
+            assert!(
+                matches!(parsed, Ok(Command::Get { query: q, target: Target::Focused }) if q == query),
+                "get {name} parsed to {parsed:?}"
+            );
+        }
+
+        assert!(matches!(
+            parse("get player --monitor=DP-2"),
+            Ok(Command::Get {
+                query: Query::Player,
+                target: Target::Connector(connector),
+            }) if connector == "DP-2"
+        ));
+    }
+
+    #[test]
+    fn parses_subscribe() {
+        let Ok(Command::Subscribe { events }) = parse("subscribe") else {
+            panic!("subscribe didn't parse");
+        };
+        assert!(events.is_empty());
+
+        let Ok(Command::Subscribe { events }) = parse("subscribe reveal weather") else {
+            panic!("subscribe with events didn't parse");
+        };
+        assert_eq!(events, ["reveal", "weather"]);
+    }
+
+    #[test]
+    fn parses_reload_css() {
+        assert!(matches!(parse("reload-css"), Ok(Command::ReloadCss)));
+    }
+
+    #[test]
+    fn parses_log_level() {
+        assert!(matches!(
+            parse("log-level"),
+            Ok(Command::LogLevel { level: None })
+        ));
+        assert!(matches!(
+            parse("log-level commashell=debug"),
+            Ok(Command::LogLevel { level: Some(level) }) if level == "commashell=debug"
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_requests() {
+        assert_eq!(error("", "empty-request"), Error::Empty);
+        assert_eq!(error("   ", "empty-request"), Error::Empty);
+    }
+
+    #[test]
+    fn rejects_unknown_verbs() {
+        assert_eq!(
+            error("flip top", "unknown-command"),
+            Error::UnknownCommand("flip".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_extra_arguments() {
+        for (request, extra) in [
+            ("toggle top right", "right"),
+            ("status now", "now"),
+            ("get player weather", "weather"),
+            ("reload-css please", "please"),
+            ("log-level info debug", "debug"),
+            ("launcher top", "top"),
+        ] {
+            assert_eq!(
+                error(request, "unexpected-argument"),
+                Error::UnexpectedArgument(extra.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert_eq!(
+            error("toggle top --output DP-2", "unknown-option"),
+            Error::UnknownOption("output".to_string())
+        );
+        assert_eq!(
+            error("reload-css --monitor DP-2", "unknown-option"),
+            Error::UnknownOption("monitor".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_arguments() {
+        assert_eq!(
+            error("toggle", "missing-argument"),
+            Error::MissingArgument("component")
+        );
+        assert_eq!(
+            error("get", "missing-argument"),
+            Error::MissingArgument("query")
+        );
+        assert_eq!(
+            error("on bottom", "unknown-component"),
+            Error::UnknownComponent("bottom".to_string())
+        );
+        assert_eq!(
+            error("get battery", "unknown-query"),
+            Error::UnknownQuery("battery".to_string())
+        );
+        assert_eq!(
+            error("subscribe reveal tracks", "unknown-event"),
+            Error::UnknownEvent("tracks".to_string())
+        );
+    }
+
+    #[test]
+    fn replies_with_the_error_kind() {
+        let reply = Reply::from(Error::UnknownCommand("flip".to_string())).to_json();
+        let reply: Value = serde_json::from_str(&reply).unwrap();
+
+        assert_eq!(reply["ok"], false);
+        assert_eq!(reply["error"]["kind"], "unknown-command");
+        assert_eq!(Reply::ok().to_json(), r#"{"ok":true}"#);
+    }
+}
@@ -55,6 +55,7 @@ impl Launcher {
 
         let text = entry.text();
         let application = self.obj().application().as_ref().unwrap().clone();
+        let config = application.config();
 
         fn launch_if_can<L: LauncherTrait>(
             model: &gio::ListStore,
@@ -72,12 +73,15 @@ impl Launcher {
             }
         }
 
-        let calc = Qalculate::new(application.clone());
+        let calc = Qalculate::new(
+            application.clone(),
+            config.launcher.calculator_prefix.clone(),
+        );
         if launch_if_can(&model, calc, &text) {
             return;
         }
 
-        let thino = Thino::new(application.clone());
+        let thino = Thino::new(application.clone(), config.launcher.thino_prefix.clone());
         if launch_if_can(&model, thino, &text) {
             return;
         }
@@ -1,8 +1,9 @@
 use astal_apps::{prelude::AppsExt, Apps};
 use gtk::{gdk, gio, prelude::*, UriLauncher};
 use std::{cmp::min, process::Command};
+use tracing::error;
 
-use crate::app::App;
+use crate::{app::App, services::Service};
 
 pub trait Launcher {
     fn can_launch(&self, term: &str) -> bool;
@@ -11,31 +12,39 @@ pub trait Launcher {
 
 pub struct Qalculate {
     app: App,
+    prefix: String,
 }
 
 impl Qalculate {
-    pub fn new(app: App) -> Self {
-        Self { app }
+    pub fn new(app: App, prefix: String) -> Self {
+        Self { app, prefix }
     }
 }
 
 impl Launcher for Qalculate {
     fn can_launch(&self, term: &str) -> bool {
-        term.starts_with("= ")
+        term.starts_with(&self.prefix)
     }
 
     fn launch(&self, term: &str) -> impl Iterator<Item = gtk::Widget> {
-        let input = term.trim_start_matches("= ");
-
-        let result = Command::new("qalc")
-            .arg(input)
-            .output()
-            .expect("failed to execute process");
-
-        let output = String::from_utf8(result.stdout).unwrap();
-        let output = output.trim_end();
-
-        let output = gtk::Button::with_label(output);
+        let input = term.strip_prefix(self.prefix.as_str()).unwrap_or(term);
+
+        let (output, available) = match self.app.unavailable(Service::Qalc) {
+            Some(reason) => (format!("Calculator unavailable: {reason}"), false),
+            None => match Command::new("qalc").arg(input).output() {
+                Ok(result) => (
+                    String::from_utf8_lossy(&result.stdout)
+                        .trim_end()
+                        .to_string(),
+                    true,
+                ),
+                Err(err) => (format!("Could not run qalc: {err}"), false),
+            },
+        };
+
+        let output = gtk::Button::with_label(&output);
+        // nothing worth copying
+        output.set_sensitive(available);
         output.add_css_class("app-entry");
         output.set_valign(gtk::Align::Center);
         output
@@ -93,21 +102,22 @@ impl Launcher for FuzzyAppSearch {
 // use obsidian + thino to log thoughts! (pro version only. I might make this configurable to support other services)
 pub struct Thino {
     app: App,
+    prefix: String,
 }
 
 impl Thino {
-    pub fn new(app: App) -> Self {
-        Self { app }
+    pub fn new(app: App, prefix: String) -> Self {
+        Self { app, prefix }
     }
 }
 
 impl Launcher for Thino {
     fn can_launch(&self, term: &str) -> bool {
-        term.starts_with("; ")
+        term.starts_with(&self.prefix)
     }
 
     fn launch(&self, term: &str) -> impl Iterator<Item = gtk::Widget> {
-        let text = term.trim_start_matches("; ");
+        let text = term.strip_prefix(self.prefix.as_str()).unwrap_or(term);
         let uri = format!("obsidian://thino?content={}&type=daily&task=false", text);
 
         let output = gtk::Button::with_label("Send to Thino");
@@ -127,7 +137,7 @@ impl Launcher for Thino {
             let uri_launcher = UriLauncher::new(&uri);
             uri_launcher.launch(None::<&gtk::Window>, None::<&gio::Cancellable>, |res| {
                 if let Err(err) = res {
-                    eprintln!("Error logging to thino: {}", err);
+                    error!(%err, "Could not log to Thino");
                 }
             });
         });
@@ -2,13 +2,13 @@ use gtk::{
     gdk::Monitor,
     gio,
     glib::{self, Object},
-    prelude::{MonitorExt, ObjectExt},
+    prelude::MonitorExt,
     subclass::prelude::ObjectSubclassIsExt,
 };
-use gtk4_layer_shell::{Edge, LayerShell};
+use gtk4_layer_shell::LayerShell;
 use vte4::{Cast, EditableExt, WidgetExt};
 
-use crate::app::App;
+use crate::{app::App, placement};
 
 mod imp;
 mod launchers;
@@ -31,22 +31,7 @@ impl Launcher {
 
         current.setup_app_entries();
 
-        current
-            .bind_property("reveal", app, "launcher-reveal")
-            .bidirectional()
-            .build();
-
-        current.init_layer_shell();
-        let anchors = [
-            (Edge::Left, false),
-            (Edge::Right, false),
-            (Edge::Top, false),
-            (Edge::Bottom, false),
-        ];
-
-        for (anchor, state) in anchors {
-            current.set_anchor(anchor, state);
-        }
+        placement::init(&current, &app.config().components.launcher, &[], false);
 
         current.set_width_request(1);
         current.set_height_request(1);
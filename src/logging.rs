@@ -0,0 +1,101 @@
+//! Logging to stderr or the systemd journal, with levels that can be changed while running.
+//!
+//! Levels are `EnvFilter` directives, e.g. `info,commashell::top=debug`. They're taken from
+//! `COMMASHELL_LOG` if it's set, otherwise from the config file, and can be changed at runtime
+//! with the `log-level` command.
+
+use std::{io::IsTerminal, sync::OnceLock};
+
+use tracing::{error, info};
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+use crate::user_config;
+
+pub const ENV_VAR: &str = "COMMASHELL_LOG";
+pub const DEFAULT_LEVEL: &str = "info";
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+pub fn parse(directives: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(directives).map_err(|err| err.to_string())
+}
+
+/// Whether the level comes from the environment, in which case the config file doesn't change it
+fn from_env() -> bool {
+    std::env::var_os(ENV_VAR).is_some()
+}
+
+/// Sets up logging. Called once, before anything is logged.
+pub fn init(config: &user_config::Log) {
+    let level = std::env::var(ENV_VAR)
+        .ok()
+        .or_else(|| config.level.clone())
+        .unwrap_or_else(|| DEFAULT_LEVEL.to_string());
+
+    // there's nowhere to log this to yet
+    let filter = parse(&level).unwrap_or_else(|err| {
+        eprintln!("Invalid log level {level:?}, using {DEFAULT_LEVEL}: {err}");
+        EnvFilter::new(DEFAULT_LEVEL)
+    });
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let journald = if config.journald {
+        match tracing_journald::layer() {
+            Ok(layer) => Some(layer),
+            Err(err) => {
+                eprintln!("Could not connect to journald, logging to stderr instead: {err}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let stderr = journald.is_none().then(|| {
+        fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::stderr().is_terminal())
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(journald)
+        .with(stderr)
+        .init();
+
+    FILTER.set(handle).expect("Logging to only be set up once");
+}
+
+/// The directives currently in use
+pub fn level() -> String {
+    FILTER
+        .get()
+        .and_then(|handle| handle.with_current(|filter| filter.to_string()).ok())
+        .unwrap_or_default()
+}
+
+pub fn set_level(directives: &str) -> Result<(), String> {
+    let filter = parse(directives)?;
+    let handle = FILTER.get().ok_or("logging isn't set up")?;
+    handle.reload(filter).map_err(|err| err.to_string())?;
+
+    info!(level = directives, "Changed log level");
+    Ok(())
+}
+
+/// Applies a reloaded config's level, unless the environment overrides it
+pub fn apply_config(old: &user_config::Log, new: &user_config::Log) {
+    if new.journald != old.journald {
+        info!("Switching to or from journald needs a restart");
+    }
+
+    if from_env() || new.level == old.level {
+        return;
+    }
+
+    let level = new.level.as_deref().unwrap_or(DEFAULT_LEVEL);
+    if let Err(err) = set_level(level) {
+        error!(%err, "Could not change log level");
+    }
+}
@@ -1,24 +1,37 @@
 mod app;
 mod app_entry;
+mod autohide;
 mod cava;
+mod client;
+mod events;
+mod ipc;
 mod launcher;
+mod logging;
 mod notification;
 mod notifications;
+mod notify;
+mod placement;
+mod registry;
 mod right;
+mod services;
+mod session;
+mod status;
+mod style;
 mod top;
+mod user_config;
 
 use std::sync::LazyLock;
 
 use app::App;
-use astal::prelude::ApplicationExt as AstalApplicationExt;
 use astal_io::prelude::ApplicationExt as AstalIOApplicationExt;
+use clap::Parser;
 use config::{APP_ID, RESOURCES_BYTES, RESOURCES_PATH};
 use gtk::glib::clone;
 use gtk::prelude::*;
 use gtk::{gio, glib};
-use launcher::Launcher;
 use tokio::sync::mpsc;
-use top::Top;
+use tracing::error;
+use user_config::UserConfig;
 #[rustfmt::skip]
 mod config;
 
@@ -32,9 +45,64 @@ fn init_resources() {
     gtk::gio::resources_register(&resource);
 }
 
-const CSS_STYLE: &str = include_str!("../data/resources/style.css");
+/// A desktop shell. Run without a command to start it, or with one to control a running shell.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Name of the instance to start or control, so several shells can run side by side
+    #[arg(long, global = true, default_value = client::DEFAULT_INSTANCE, value_parser = parse_name)]
+    instance_name: String,
+
+    /// Settings profile to start the shell with, e.g. `presentation`
+    #[arg(long, value_parser = parse_name)]
+    profile: Option<String>,
+
+    /// Print replies as raw JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Option<client::Command>,
+}
+
+/// Instance and profile names end up in socket, D-Bus and settings paths, so keep them simple
+fn parse_name(name: &str) -> Result<String, String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(name.to_string())
+    } else {
+        Err("may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+fn already_running(instance: &str) -> glib::ExitCode {
+    eprintln!(
+        "commashell: instance {instance} is already running, \
+         control it with `commashell --instance-name {instance} msg ...`"
+    );
+    glib::ExitCode::FAILURE
+}
 
 fn main() -> glib::ExitCode {
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        return client::run(&cli.instance_name, command, cli.json);
+    }
+
+    if client::is_running(&cli.instance_name) {
+        return already_running(&cli.instance_name);
+    }
+
+    // the rest of the config is loaded (and any errors in it logged) once the app starts
+    logging::init(
+        &UserConfig::load(&UserConfig::path())
+            .unwrap_or_default()
+            .log,
+    );
+
     init_resources();
     adw::init().expect("To initialize Adwaita");
 
@@ -42,37 +110,52 @@ fn main() -> glib::ExitCode {
     let (sender, reciever) = mpsc::channel(5);
     let app = App::builder()
         .application_id(APP_ID)
+        .instance_name(cli.instance_name.as_str())
+        .settings_profile(cli.profile)
+        // instances are kept unique by their socket instead, so several can share the app id
+        .flags(gio::ApplicationFlags::NON_UNIQUE)
         .resource_base_path(RESOURCES_PATH)
         .build(reciever, sender);
 
-    app.acquire_socket().expect("To acquire socket");
+    if let Err(err) = app.acquire_socket() {
+        if err.matches(gio::IOErrorEnum::Exists) {
+            return already_running(&cli.instance_name);
+        }
+
+        error!(%err, "Could not acquire socket");
+        return glib::ExitCode::FAILURE;
+    }
 
     match app.register(gio::Cancellable::NONE) {
         Ok(_) => {}
-        Err(err) => eprintln!("Registration error, {}", err),
+        Err(err) => error!(%err, "Could not register the application"),
     }
 
-    app.apply_css(CSS_STYLE, false);
-
     app.connect_activate(clone!(
         #[strong]
         app,
         move |_| {
-            for monitor in &app.monitors() {
-                let top = Top::new(&app, monitor);
-                app.add_window(&top);
-
-                let right = right::Right::new(&app, monitor);
-                app.add_window(&right);
-
-                let notifications = notifications::Notifications::new(&app, monitor);
-                app.add_window(&notifications);
-
-                let launcher = Launcher::new(&app, monitor);
-                app.add_window(&launcher);
-            }
+            // asking the system bus what's running can be slow, so don't block the main loop on it
+            let hold = app.hold();
+            glib::spawn_future_local(clone!(
+                #[strong]
+                app,
+                async move {
+                    app.detect_services().await;
+                    app.watch_config();
+                    app.watch_css();
+                    events::watch_app(&app);
+                    app.load_session();
+                    app.watch_monitors();
+                    top::watch_system(&app);
+                    top::watch_battery(&app);
+                    app.restore_launcher();
+                    drop(hold);
+                }
+            ));
         }
     ));
 
-    app.run()
+    // our arguments were already handled above, so don't let GApplication see them
+    app.run_with_args(&std::env::args().take(1).collect::<Vec<_>>())
 }
@@ -6,10 +6,10 @@ use gtk::{
     prelude::GtkWindowExt,
     subclass::prelude::*,
 };
-use gtk4_layer_shell::{Edge, LayerShell};
+use tracing::warn;
 use vte4::{Cast, ListModelExtManual};
 
-use crate::{app::App, notification::Notification};
+use crate::{app::App, notification::Notification, placement, user_config::Edge};
 
 mod imp;
 
@@ -24,55 +24,70 @@ impl Notifications {
     pub fn new(app: &App, monitor: &Monitor) -> Self {
         let current: Self = Object::builder().property("application", app).build();
         current.set_monitor(monitor);
-        let notifd = astal_notifd::functions::default().expect("Expected to get default Notifd");
-
-        notifd.connect_notified(glib::clone!(
-            #[weak]
-            current,
-            move |notifd, notif, _replaced| {
-                current
-                    .notification_widgets()
-                    .append(&notifd.notification(notif).unwrap());
-            }
-        ));
-
-        notifd.connect_resolved(glib::clone!(
-            #[weak]
-            current,
-            move |_notifd, notif, _reason| {
-                let notifications = current.notification_widgets();
-                let Some(index) = notifications
-                    .iter::<astal_notifd::Notification>()
-                    .position(|n| n.is_ok_and(|n| n.id() == notif))
-                else {
-                    eprintln!("Notification not found: {}", notif);
-                    return;
-                };
-
-                notifications.remove(index as u32);
-            }
-        ));
-
-        current.init_layer_shell();
-        let anchors = [
-            (Edge::Left, false),
-            (Edge::Right, true),
-            (Edge::Top, true),
-            (Edge::Bottom, false),
-        ];
-
-        for (anchor, state) in anchors {
-            current.set_anchor(anchor, state);
+        let notifd = astal_notifd::functions::default();
+
+        if let Some(notifd) = &notifd {
+            notifd.connect_notified(glib::clone!(
+                #[weak]
+                current,
+                move |notifd, notif, _replaced| {
+                    current
+                        .notification_widgets()
+                        .append(&notifd.notification(notif).unwrap());
+                }
+            ));
+
+            notifd.connect_resolved(glib::clone!(
+                #[weak]
+                current,
+                move |_notifd, notif, _reason| {
+                    let notifications = current.notification_widgets();
+                    let Some(index) = notifications
+                        .iter::<astal_notifd::Notification>()
+                        .position(|n| n.is_ok_and(|n| n.id() == notif))
+                    else {
+                        warn!(id = notif, "Resolved notification not found");
+                        return;
+                    };
+
+                    notifications.remove(index as u32);
+                }
+            ));
         }
 
-        current.auto_exclusive_zone_enable();
+        // notifications stack down from the top corner of their edge, and keep their column clear
+        let layout = &app.config().components.notifications;
+        let edge = layout.edge.unwrap_or(Edge::Right);
+        placement::init(&current, layout, &[edge, Edge::Top], true);
 
-        current.present();
+        // without a notification daemon there's never anything to show
+        if notifd.is_some() {
+            current.present();
+        }
 
         current
     }
 
-    fn notification_widgets(&self) -> gio::ListStore {
+    /// Shows the notifications with `ids` again, e.g. after a restart.
+    /// Ones the notification daemon has since forgotten are skipped.
+    pub fn restore(&self, ids: &[u32]) {
+        let Some(notifd) = astal_notifd::functions::default() else {
+            return;
+        };
+
+        let notifications = self.notification_widgets();
+        for &id in ids {
+            let shown = notifications
+                .iter::<astal_notifd::Notification>()
+                .any(|n| n.is_ok_and(|n| n.id() == id));
+
+            if let Some(notification) = notifd.notification(id).filter(|_| !shown) {
+                notifications.append(&notification);
+            }
+        }
+    }
+
+    pub fn notification_widgets(&self) -> gio::ListStore {
         self.imp()
             .notifications
             .borrow()
@@ -0,0 +1,75 @@
+//! Sending notifications, e.g. for a low battery.
+//!
+//! They go out over the session bus like any other app's, so the shell's own notification daemon
+//! shows them, or whichever daemon is running instead.
+
+use std::collections::HashMap;
+
+use gtk::{gio, glib, prelude::*};
+use tracing::error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+    /// An icon name
+    pub icon: String,
+    pub urgency: Urgency,
+}
+
+impl Notification {
+    /// Sends the notification without waiting for it, logging if it couldn't be sent
+    pub fn send_later(self) {
+        glib::spawn_future_local(async move {
+            if let Err(err) = self.send().await {
+                error!(%err, summary = %self.summary, "Could not send notification");
+            }
+        });
+    }
+
+    pub async fn send(&self) -> Result<(), glib::Error> {
+        let bus = gio::bus_get_future(gio::BusType::Session).await?;
+
+        let urgency: u8 = match self.urgency {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Critical => 2,
+        };
+        let hints = HashMap::from([("urgency".to_string(), urgency.to_variant())]);
+
+        bus.call_future(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            "org.freedesktop.Notifications",
+            "Notify",
+            Some(
+                &(
+                    "commashell",
+                    // not replacing anything
+                    0u32,
+                    self.icon.as_str(),
+                    self.summary.as_str(),
+                    self.body.as_str(),
+                    Vec::<String>::new(),
+                    hints,
+                    // the daemon's default timeout
+                    -1i32,
+                )
+                    .to_variant(),
+            ),
+            glib::VariantTy::new("(u)").ok(),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
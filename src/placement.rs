@@ -0,0 +1,51 @@
+//! Layer-shell placement of the shell's windows, as set in the `[components]` config.
+
+use gtk::prelude::*;
+use gtk4_layer_shell::LayerShell;
+
+use crate::user_config::{Edge, Layout};
+
+const EDGES: [Edge; 4] = [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right];
+
+/// Makes `window` a layer-shell surface anchored to `anchors`, on the configured layer
+pub fn init(
+    window: &impl IsA<gtk::Window>,
+    layout: &Layout,
+    anchors: &[Edge],
+    default_exclusive: bool,
+) {
+    window.init_layer_shell();
+
+    for edge in EDGES {
+        window.set_anchor(edge.into(), anchors.contains(&edge));
+    }
+
+    window.set_layer(layout.layer.into());
+
+    // an auto-hiding panel would push everything around whenever it's revealed
+    let exclusive = default_exclusive && !layout.auto_hide;
+    if layout.exclusive.unwrap_or(exclusive) {
+        window.auto_exclusive_zone_enable();
+    }
+}
+
+/// Docks `window` along the whole of its configured edge, or `default_edge`, and returns that edge.
+///
+/// The window gets an `edge-<name>` class so it can be styled to match.
+pub fn dock(
+    window: &impl IsA<gtk::Window>,
+    layout: &Layout,
+    default_edge: Edge,
+    default_exclusive: bool,
+) -> Edge {
+    let edge = layout.edge.unwrap_or(default_edge);
+    let anchors: Vec<Edge> = EDGES
+        .into_iter()
+        .filter(|anchor| *anchor == edge || anchor.is_horizontal() != edge.is_horizontal())
+        .collect();
+
+    init(window, layout, &anchors, default_exclusive);
+    window.add_css_class(&format!("edge-{}", edge.name()));
+
+    edge
+}
@@ -0,0 +1,146 @@
+//! Every shell window, by monitor and then by component.
+
+use std::collections::HashMap;
+
+use astal_mpris::prelude::PlayerExt;
+use astal_notifd::prelude::*;
+use gtk::{gdk::Monitor, glib, prelude::*};
+
+use crate::{
+    app::Component, ipc, launcher::Launcher, notifications::Notifications, right::Right,
+    session::OutputState, top::Top,
+};
+
+/// The windows created for a single monitor
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub monitor: Monitor,
+    windows: HashMap<Component, gtk::Window>,
+}
+
+impl Output {
+    pub fn new(monitor: &Monitor) -> Self {
+        Self {
+            monitor: monitor.clone(),
+            windows: HashMap::new(),
+        }
+    }
+
+    pub fn connector(&self) -> Option<glib::GString> {
+        self.monitor.connector()
+    }
+
+    pub fn insert(&mut self, component: Component, window: &impl IsA<gtk::Window>) {
+        self.windows.insert(component, window.clone().upcast());
+    }
+
+    pub fn window(&self, component: Component) -> Result<gtk::Window, ipc::Error> {
+        self.windows
+            .get(&component)
+            .cloned()
+            .ok_or_else(|| self.missing(component))
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &gtk::Window> {
+        self.windows.values()
+    }
+
+    pub fn top(&self) -> Result<Top, ipc::Error> {
+        self.typed_window(Component::Top)
+    }
+
+    pub fn right(&self) -> Result<Right, ipc::Error> {
+        self.typed_window(Component::Right)
+    }
+
+    pub fn notifications(&self) -> Result<Notifications, ipc::Error> {
+        self.typed_window(Component::Notifications)
+    }
+
+    pub fn launcher(&self) -> Result<Launcher, ipc::Error> {
+        self.typed_window(Component::Launcher)
+    }
+
+    /// What's shown on this monitor, to be put back after a restart or reconnect
+    pub fn state(&self) -> OutputState {
+        let top = self.top().ok();
+        let right = self.right().ok();
+
+        OutputState {
+            top: top.as_ref().is_some_and(|top| top.reveal()),
+            right: right.as_ref().is_some_and(|right| right.reveal()),
+            player: right
+                .and_then(|right| right.player())
+                .and_then(|player| player.bus_name())
+                .map(|bus_name| bus_name.to_string()),
+            wallpaper: top
+                .map(|top| top.wallpaper())
+                .filter(|wallpaper| !wallpaper.is_empty())
+                .map(Into::into),
+            notifications: self
+                .notifications()
+                .map(|notifications| {
+                    notifications
+                        .notification_widgets()
+                        .iter::<astal_notifd::Notification>()
+                        .filter_map(Result::ok)
+                        .map(|notification| notification.id())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn typed_window<T: IsA<gtk::Window>>(&self, component: Component) -> Result<T, ipc::Error> {
+        self.window(component)?
+            .downcast::<T>()
+            .map_err(|_| self.missing(component))
+    }
+
+    fn missing(&self, component: Component) -> ipc::Error {
+        ipc::Error::MissingWindow {
+            component,
+            monitor: self.connector().map(|c| c.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Registry {
+    outputs: Vec<Output>,
+}
+
+impl Registry {
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    pub fn insert(&mut self, output: Output) {
+        self.outputs.push(output);
+    }
+
+    pub fn remove(&mut self, monitor: &Monitor) -> Option<Output> {
+        let index = self
+            .outputs
+            .iter()
+            .position(|output| output.monitor == *monitor)?;
+
+        Some(self.outputs.remove(index))
+    }
+
+    pub fn contains(&self, monitor: &Monitor) -> bool {
+        self.get(monitor).is_some()
+    }
+
+    pub fn get(&self, monitor: &Monitor) -> Option<&Output> {
+        self.outputs
+            .iter()
+            .find(|output| output.monitor == *monitor)
+    }
+
+    pub fn by_connector(&self, connector: &str) -> Option<&Output> {
+        self.outputs
+            .iter()
+            .find(|output| output.connector().is_some_and(|c| c.as_str() == connector))
+    }
+}
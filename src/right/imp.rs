@@ -7,9 +7,10 @@ use gtk::glib::{Properties, SpawnFlags};
 use gtk::subclass::prelude::*;
 use gtk::{gdk, gio, prelude::*};
 use gtk::{glib, CompositeTemplate};
+use tracing::error;
 use vte4::{PtyFlags, TerminalExt, TerminalExtManual};
 
-use crate::cava::Cava;
+use crate::{autohide::AutoHide, cava::Cava};
 
 #[derive(CompositeTemplate, Properties, Default, Debug)]
 #[template(resource = "/in/wobbl/commashell/ui/right.ui")]
@@ -17,8 +18,16 @@ use crate::cava::Cava;
 pub struct Right {
     #[property(get, set, nullable)]
     pub(super) player: RefCell<Option<astal_mpris::Player>>,
+    /// Bus name of the player to show once it appears, instead of the highest ranked one
+    pub(super) restored_player: RefCell<Option<String>>,
+    /// What `player` is connected to, undone before another player is shown
+    pub(super) player_handlers: RefCell<Vec<glib::SignalHandlerId>>,
+    pub(super) player_binding: RefCell<Option<glib::Binding>>,
+    pub auto_hide: RefCell<Option<AutoHide>>,
     // template children
     #[template_child]
+    pub revealer: TemplateChild<gtk::Revealer>,
+    #[template_child]
     pub default_text: TemplateChild<gtk::Label>,
     #[template_child(id = "player")]
     pub player_overlay: TemplateChild<gtk::Overlay>,
@@ -157,7 +166,7 @@ impl ObjectImpl for Right {
             None::<&gio::Cancellable>,
             |output| {
                 if let Err(err) = output {
-                    eprintln!("Error when setting up lyrics: {}", err);
+                    error!(%err, "Could not set up lyrics");
                 }
             },
         );
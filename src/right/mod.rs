@@ -1,4 +1,4 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, time::Duration};
 
 use astal::prelude::*;
 use astal_mpris::{
@@ -9,13 +9,12 @@ use gtk::{
     gdk::Monitor,
     gio,
     glib::{self, Object},
-    prelude::{GtkWindowExt, MonitorExt, ObjectExt},
+    prelude::{CastNone, GtkWindowExt, MonitorExt, ObjectExt},
     subclass::prelude::ObjectSubclassIsExt,
 };
-use gtk4_layer_shell::{Edge, LayerShell};
 use vte4::WidgetExt;
 
-use crate::app::App;
+use crate::{app::App, autohide::AutoHide, placement, user_config::Edge};
 
 mod imp;
 
@@ -28,8 +27,6 @@ glib::wrapper! {
 
 impl Right {
     pub fn new(app: &App, monitor: &Monitor) -> Self {
-        let mpris = astal_mpris::functions::default().expect("Expected to get default Mpris");
-
         let current: Self = Object::builder()
             .property("application", app)
             .property("default-height", monitor.geometry().height())
@@ -37,76 +34,126 @@ impl Right {
 
         current.set_monitor(monitor);
 
-        current
-            .bind_property("reveal", app, "right-reveal")
-            .bidirectional()
-            .build();
-
-        connect_players(&mpris, &current);
-
-        mpris.connect_players_notify(glib::clone!(
-            #[weak]
-            current,
-            move |mpris| connect_players(mpris, &current)
-        ));
-
-        mpris.connect_player_closed(glib::clone!(
-            #[weak]
-            current,
-            move |_mpris, player| {
-                let current_player = current.imp().player.borrow();
-                let match_player = current_player.clone().is_some_and(|p| p == *player);
-                drop(current_player);
-
-                if match_player {
-                    current.imp().player_overlay.set_visible(false);
-                    current.imp().lyrics_overlay.set_visible(false);
-                    current.imp().default_text.set_visible(true);
-                    current.set_player(None::<Player>);
+        if let Some(mpris) = astal_mpris::functions::default() {
+            connect_players(&mpris, &current);
+
+            mpris.connect_players_notify(glib::clone!(
+                #[weak]
+                current,
+                move |mpris| connect_players(mpris, &current)
+            ));
+
+            mpris.connect_player_closed(glib::clone!(
+                #[weak]
+                current,
+                move |_mpris, player| {
+                    let current_player = current.imp().player.borrow();
+                    let match_player = current_player.clone().is_some_and(|p| p == *player);
+                    drop(current_player);
+
+                    if match_player {
+                        current.imp().player_overlay.set_visible(false);
+                        current.imp().lyrics_overlay.set_visible(false);
+                        current.imp().default_text.set_visible(true);
+                        current.set_player(None::<Player>);
+                    }
                 }
-            }
-        ));
-
-        current.init_layer_shell();
-        let anchors = [
-            (Edge::Left, false),
-            (Edge::Right, true),
-            (Edge::Top, true),
-            (Edge::Bottom, true),
-        ];
-
-        for (anchor, state) in anchors {
-            current.set_anchor(anchor, state);
+            ));
+        } else {
+            current.imp().player_overlay.set_visible(false);
+            current.imp().lyrics_overlay.set_visible(false);
+            current
+                .imp()
+                .default_text
+                .set_label("Music controls are unavailable");
+        }
+
+        let layout = &app.config().components.right;
+        let edge = placement::dock(&current, layout, Edge::Right, true);
+        current
+            .imp()
+            .revealer
+            .set_transition_type(if edge == Edge::Left {
+                gtk::RevealerTransitionType::SlideLeft
+            } else {
+                gtk::RevealerTransitionType::SlideRight
+            });
+
+        if layout.auto_hide {
+            current.imp().auto_hide.replace(Some(AutoHide::attach(
+                &current,
+                edge,
+                Duration::from_millis(layout.hide_delay),
+                Duration::from_millis(layout.peek_duration),
+            )));
         }
 
         current.set_default_width(1);
-        current.auto_exclusive_zone_enable();
 
         current.present();
 
         current
     }
+
+    /// Briefly shows the panel if it auto-hides
+    pub fn peek(&self) {
+        if let Some(auto_hide) = self.imp().auto_hide.borrow().as_ref() {
+            auto_hide.peek();
+        }
+    }
+
+    /// Shows the player with `bus_name` as soon as it's open, e.g. after a restart
+    pub fn restore_player(&self, bus_name: String) {
+        self.imp().restored_player.replace(Some(bus_name));
+        self.select_player();
+    }
+
+    /// Picks the player to show again, e.g. after the player priorities changed
+    pub fn select_player(&self) {
+        if let Some(mpris) = astal_mpris::functions::default() {
+            connect_players(&mpris, self);
+        }
+    }
 }
 
 fn connect_players(mpris: &Mpris, current: &Right) {
-    let Some(player) = mpris
-        .players()
-        .iter()
-        .max_by_key(|player| {
-            let i = player.identity();
-            if i.as_ref().is_some_and(|i| i.contains("Feishin")) {
-                200
-            } else if i.as_ref().is_some_and(|i| i.contains("strawberry")) {
-                100
-            } else {
-                0
-            }
-        })
-        .cloned()
-    else {
+    let config = current
+        .application()
+        .and_downcast::<App>()
+        .map(|app| app.config())
+        .unwrap_or_default();
+
+    let players = mpris.players();
+    let restored = current
+        .imp()
+        .restored_player
+        .borrow()
+        .as_ref()
+        .and_then(|bus_name| {
+            players
+                .iter()
+                .find(|player| player.bus_name().is_some_and(|b| b.as_str() == bus_name))
+                .cloned()
+        });
+
+    if restored.is_some() {
+        current.imp().restored_player.replace(None);
+    }
+
+    let Some(player) = restored.or_else(|| {
+        players
+            .iter()
+            .max_by_key(|player| {
+                config
+                    .player
+                    .rank(player.identity().as_ref().map(|i| i.as_str()))
+            })
+            .cloned()
+    }) else {
         current.imp().player_overlay.set_visible(false);
         current.imp().lyrics_overlay.set_visible(false);
         current.imp().default_text.set_visible(true);
+        disconnect_player(current);
         current.set_player(None::<Player>);
         return;
     };
@@ -117,6 +164,7 @@ fn connect_players(mpris: &Mpris, current: &Right) {
     }
     drop(current_player);
 
+    disconnect_player(current);
     current.set_player(Some(player.clone()));
 
     if player.length() > 0.0 {
@@ -125,7 +173,7 @@ fn connect_players(mpris: &Mpris, current: &Right) {
         current.set_length(0.0);
     }
 
-    player.connect_length_notify(glib::clone!(
+    let length = player.connect_length_notify(glib::clone!(
         #[weak]
         current,
         move |player| {
@@ -139,12 +187,12 @@ fn connect_players(mpris: &Mpris, current: &Right) {
     ));
 
     current.imp().seeker.borrow().set_value(player.position());
-    player
+    let position = player
         .bind_property("position", &current.imp().seeker.borrow().get(), "value")
         .build();
 
     current.set_playing(player.playback_status() == PlaybackStatus::Playing);
-    player.connect_playback_status_notify(glib::clone!(
+    let playback_status = player.connect_playback_status_notify(glib::clone!(
         #[weak]
         current,
         move |player| {
@@ -152,7 +200,35 @@ fn connect_players(mpris: &Mpris, current: &Right) {
         }
     ));
 
+    // a new track is worth showing the panel for
+    let title = player.connect_title_notify(glib::clone!(
+        #[weak]
+        current,
+        move |_player| current.peek()
+    ));
+
+    current
+        .imp()
+        .player_handlers
+        .replace(vec![length, playback_status, title]);
+    current.imp().player_binding.replace(Some(position));
+
     current.imp().player_overlay.set_visible(true);
     current.imp().lyrics_overlay.set_visible(true);
     current.imp().default_text.set_visible(false);
 }
+
+/// Stops the shown player from updating `current`, so another one can take its place
+fn disconnect_player(current: &Right) {
+    let imp = current.imp();
+    if let Some(binding) = imp.player_binding.take() {
+        binding.unbind();
+    }
+
+    let handlers = imp.player_handlers.take();
+    if let Some(player) = imp.player.borrow().as_ref() {
+        for handler in handlers {
+            player.disconnect(handler);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Optional backends the components depend on.
+//!
+//! Each one is checked once at startup, before any windows are created. A component whose backend is missing shows a placeholder
+//! or hides itself instead of taking the whole shell down, and the reason can be asked for with
+//! `get services`.
+
+use std::collections::BTreeMap;
+
+use futures_util::future;
+use gtk::{gio, glib, prelude::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Service {
+    /// Media players, for the music panel
+    Mpris,
+    /// The notification daemon
+    Notifd,
+    /// The audio visualiser behind the lyrics
+    Cava,
+    /// The launcher's calculator
+    Qalc,
+    /// Connection names and types for the network module
+    NetworkManager,
+    /// Battery status, which is read from the kernel without it
+    UPower,
+    /// Mounting and ejecting removable drives
+    UDisks,
+}
+
+impl Service {
+    pub const ALL: [Service; 7] = [
+        Service::Mpris,
+        Service::Notifd,
+        Service::Cava,
+        Service::Qalc,
+        Service::NetworkManager,
+        Service::UPower,
+        Service::UDisks,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Service::Mpris => "mpris",
+            Service::Notifd => "notifd",
+            Service::Cava => "cava",
+            Service::Qalc => "qalc",
+            Service::NetworkManager => "networkmanager",
+            Service::UPower => "upower",
+            Service::UDisks => "udisks",
+        }
+    }
+
+    /// Checks whether the backend can be used, returning why not if it can't
+    async fn detect(&self) -> Result<(), String> {
+        let available = match self {
+            Service::Mpris => astal_mpris::functions::default().is_some(),
+            Service::Notifd => astal_notifd::functions::default().is_some(),
+            Service::Cava => astal_cava::functions::default().is_some(),
+            Service::Qalc => glib::find_program_in_path("qalc").is_some(),
+            Service::NetworkManager => has_owner("org.freedesktop.NetworkManager").await,
+            Service::UPower => has_owner("org.freedesktop.UPower").await,
+            // started on demand, so it usually isn't running yet
+            Service::UDisks => activatable("org.freedesktop.UDisks2").await,
+        };
+
+        if available {
+            return Ok(());
+        }
+
+        Err(match self {
+            Service::Mpris => "could not connect to MPRIS over D-Bus",
+            Service::Notifd => "could not start or connect to a notification daemon",
+            Service::Cava => "could not start cava, is libcava installed?",
+            Service::Qalc => "qalc was not found in PATH, is libqalculate installed?",
+            Service::NetworkManager => "NetworkManager is not running on the system bus",
+            Service::UPower => "UPower is not running on the system bus",
+            Service::UDisks => "UDisks2 can't be started on the system bus, is udisks2 installed?",
+        }
+        .to_string())
+    }
+}
+
+/// Whether something is running under `name` on the system bus
+async fn has_owner(name: &str) -> bool {
+    let reply = call_bus("NameHasOwner", Some((name,).to_variant()), "(b)").await;
+    reply.is_ok_and(|reply| reply.child_value(0).get::<bool>() == Some(true))
+}
+
+/// Whether something is running under `name` on the system bus, or can be started there
+async fn activatable(name: &str) -> bool {
+    if has_owner(name).await {
+        return true;
+    }
+
+    let reply = call_bus("ListActivatableNames", None, "(as)").await;
+    reply.is_ok_and(|reply| {
+        reply
+            .child_value(0)
+            .get::<Vec<String>>()
+            .is_some_and(|names| names.iter().any(|activatable| activatable == name))
+    })
+}
+
+/// Calls `method` on the system bus itself
+async fn call_bus(
+    method: &str,
+    args: Option<glib::Variant>,
+    reply_type: &str,
+) -> Result<glib::Variant, glib::Error> {
+    let bus = gio::bus_get_future(gio::BusType::System).await?;
+    bus.call_future(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        method,
+        args.as_ref(),
+        glib::VariantTy::new(reply_type).ok(),
+        gio::DBusCallFlags::NONE,
+        1000,
+    )
+    .await
+}
+
+/// Which services could not be used, and why
+#[derive(Debug, Default)]
+pub struct Services {
+    unavailable: BTreeMap<Service, String>,
+}
+
+impl Services {
+    /// Checks every service at once, so a slow system bus is only waited on once
+    pub async fn detect() -> Self {
+        let results = future::join_all(
+            Service::ALL.map(|service| async move { (service, service.detect().await) }),
+        )
+        .await;
+
+        let unavailable = results
+            .into_iter()
+            .filter_map(|(service, result)| result.err().map(|reason| (service, reason)))
+            .collect();
+
+        Self { unavailable }
+    }
+
+    /// Why `service` can't be used, or None if it can
+    pub fn unavailable(&self, service: Service) -> Option<&str> {
+        self.unavailable.get(&service).map(String::as_str)
+    }
+}
@@ -0,0 +1,65 @@
+//! State that should survive a restart, so a crash or a config change doesn't move things around.
+//!
+//! It's kept in `$XDG_STATE_HOME/commashell/<instance>.json`, written shortly after anything in it
+//! changes and read once at startup.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// By connector, including monitors that aren't connected right now
+    pub outputs: BTreeMap<String, OutputState>,
+    /// The connector of the monitor the launcher is open on, if it's open
+    pub launcher: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputState {
+    pub top: bool,
+    pub right: bool,
+    /// Bus name of the player shown in the music panel
+    pub player: Option<String>,
+    pub wallpaper: Option<PathBuf>,
+    /// Ids of the notifications shown, oldest first. The notification daemon keeps their contents.
+    pub notifications: Vec<u32>,
+}
+
+impl Session {
+    pub fn path(instance: &str) -> PathBuf {
+        glib::user_state_dir()
+            .join("commashell")
+            .join(format!("{instance}.json"))
+    }
+
+    /// Reads the session at `path`, or an empty one if there's nothing there yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.to_string()),
+        };
+
+        serde_json::from_str(&text).map_err(|err| err.to_string())
+    }
+
+    /// Writes the session to `path`, replacing the old file in one go so a crash can't leave
+    /// half of it behind
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp, path)
+    }
+}
@@ -0,0 +1,137 @@
+//! Read-only snapshots of the shell state, built from the components' properties.
+//!
+//! These are returned by the `status` and `get` IPC commands.
+
+use astal_mpris::prelude::PlayerExt;
+use astal_notifd::prelude::*;
+use gtk::prelude::*;
+use serde_json::{json, Value};
+
+use crate::{
+    app::App, notifications::Notifications, registry::Output, right::Right, services::Service,
+    top::Top,
+};
+
+/// Everything at once, with per-monitor values taken from `output`
+pub fn status(app: &App, output: Option<&Output>) -> Value {
+    json!({
+        "launcher": launcher(app),
+        "monitors": monitors(app),
+        "player": output.and_then(|output| output.right().ok()).map(|right| player(&right)),
+        "notifications": output
+            .and_then(|output| output.notifications().ok())
+            .map(|n| notifications(&n)),
+        "weather": output.and_then(|output| output.top().ok()).map(|top| weather(&top)),
+        "services": services(app),
+    })
+}
+
+pub fn launcher(app: &App) -> Value {
+    json!({ "revealed": app.launcher_reveal() })
+}
+
+pub fn monitors(app: &App) -> Value {
+    app.outputs()
+        .iter()
+        .map(|output| {
+            let state = output.state();
+
+            json!({
+                "connector": output.connector().map(|c| c.to_string()),
+                "top": { "revealed": state.top },
+                "right": { "revealed": state.right },
+            })
+        })
+        .collect()
+}
+
+/// The player selected in the music panel, or null if nothing is playing
+pub fn player(right: &Right) -> Value {
+    let Some(player) = right.player() else {
+        return Value::Null;
+    };
+
+    json!({
+        "identity": player.identity().map(|s| s.to_string()),
+        "bus_name": player.bus_name().map(|s| s.to_string()),
+        "title": player.title().map(|s| s.to_string()),
+        "artist": player.artist().map(|s| s.to_string()),
+        "album": player.album().map(|s| s.to_string()),
+        "playing": right.playing(),
+        "position": player.position(),
+        "length": right.length(),
+    })
+}
+
+pub fn notifications(notifications: &Notifications) -> Value {
+    let list: Vec<Value> = notifications
+        .notification_widgets()
+        .iter::<astal_notifd::Notification>()
+        .filter_map(Result::ok)
+        .map(|notification| self::notification(&notification))
+        .collect();
+
+    json!({
+        "count": list.len(),
+        "notifications": list,
+    })
+}
+
+pub fn notification(notification: &astal_notifd::Notification) -> Value {
+    json!({
+        "id": notification.id(),
+        "app_name": notification.app_name().map(|s| s.to_string()),
+        "summary": notification.summary().map(|s| s.to_string()),
+        "urgency": match notification.urgency() {
+            astal_notifd::Urgency::Low => "low",
+            astal_notifd::Urgency::Normal => "normal",
+            astal_notifd::Urgency::Critical => "critical",
+            _ => "other",
+        },
+    })
+}
+
+/// The weather as of the last fetch, or null if it hasn't been fetched yet
+pub fn weather(top: &Top) -> Value {
+    if top.weather_updated() == 0 {
+        return Value::Null;
+    }
+
+    json!({
+        "location": top.location(),
+        "updated": top.weather_updated(),
+        "unit": if top.use_metric_units() { "celsius" } else { "fahrenheit" },
+        "temperature": top.weather_temp_desc(),
+        "description": top.weather_desc(),
+        "icon": top.weather_icon(),
+        "feels_like": top.feels_like(),
+        "humidity": top.humidity_value(),
+        "cloud_cover": top.cloud_cover_value(),
+        "uv_index": top.uv_value(),
+        // the bar's temperature range is always in celsius
+        "celsius": {
+            "current": top.weather_temp(),
+            "min": top.weather_temp_min(),
+            "max": top.weather_temp_max(),
+        },
+    })
+}
+
+/// Whether each optional backend can be used, and why not if it can't
+pub fn services(app: &App) -> Value {
+    Service::ALL
+        .into_iter()
+        .map(|service| {
+            let reason = app.unavailable(service);
+
+            (
+                service.name().to_string(),
+                json!({
+                    "available": reason.is_none(),
+                    "reason": reason,
+                }),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
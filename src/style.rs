@@ -0,0 +1,98 @@
+//! The built-in stylesheets, and the user's overrides applied on top of them.
+//!
+//! Like libadwaita, `style-dark.css` is only applied on top of `style.css` while the dark
+//! colour scheme is in use.
+
+use std::{fs, io, path::PathBuf};
+
+use astal::prelude::ApplicationExt as AstalApplicationExt;
+use gtk::{gio, glib, prelude::*};
+
+use crate::{app::App, config::PROFILE, ipc};
+
+const BUILTIN: &str = include_str!("../data/resources/style.css");
+const BUILTIN_DARK: &str = include_str!("../data/resources/style-dark.css");
+
+/// A built-in stylesheet in the source tree.
+///
+/// Only development builds read these, so they can be worked on without rebuilding.
+fn source_path(name: &str) -> Option<PathBuf> {
+    (PROFILE == "Devel")
+        .then(|| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/data/resources")).join(name))
+}
+
+fn user_path(name: &str) -> PathBuf {
+    glib::user_config_dir().join("commashell").join(name)
+}
+
+/// Every stylesheet that should be re-applied when it changes
+pub fn paths() -> Vec<PathBuf> {
+    ["style.css", "style-dark.css"]
+        .into_iter()
+        .flat_map(|name| source_path(name).into_iter().chain([user_path(name)]))
+        .collect()
+}
+
+fn builtin(name: &str, included: &str) -> String {
+    source_path(name)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .unwrap_or_else(|| included.to_string())
+}
+
+fn user(name: &str) -> Result<Option<String>, ipc::Error> {
+    let path = user_path(name);
+    match fs::read_to_string(&path) {
+        Ok(css) => Ok(Some(css)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(ipc::Error::Stylesheet(format!(
+            "{}: {}",
+            path.display(),
+            err
+        ))),
+    }
+}
+
+/// Replaces whatever stylesheets were applied with the built-in ones and the user's overrides.
+///
+/// Everything is read before anything is applied, so a file that can't be read keeps the current style.
+pub fn apply(app: &App) -> Result<(), ipc::Error> {
+    let dark = adw::StyleManager::default().is_dark();
+
+    let mut sheets = vec![builtin("style.css", BUILTIN)];
+    if dark {
+        sheets.push(builtin("style-dark.css", BUILTIN_DARK));
+    }
+
+    sheets.extend(user("style.css")?);
+    if dark {
+        sheets.extend(user("style-dark.css")?);
+    }
+
+    for (i, css) in sheets.iter().enumerate() {
+        app.apply_css(css, i == 0);
+    }
+
+    Ok(())
+}
+
+/// Makes the shell light or dark according to the `color-scheme` setting
+pub fn follow_color_scheme(settings: &gio::Settings) {
+    settings
+        .bind(
+            "color-scheme",
+            &adw::StyleManager::default(),
+            "color-scheme",
+        )
+        .flags(gio::SettingsBindFlags::GET)
+        .mapping(|variant, _| {
+            let scheme = match variant.str()? {
+                "light" => adw::ColorScheme::ForceLight,
+                "dark" => adw::ColorScheme::ForceDark,
+                // follows the desktop, light if it has no preference
+                _ => adw::ColorScheme::Default,
+            };
+
+            Some(scheme.to_value())
+        })
+        .build();
+}
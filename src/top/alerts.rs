@@ -0,0 +1,289 @@
+//! Warnings for resources that stay past a threshold, from the `[[alerts]]` rules in the config.
+
+use std::time::{Duration, Instant};
+
+use crate::user_config::{Alert, Resource};
+
+/// How far a firing rule's reading has to drop below the threshold before it stops firing, in
+/// the rule's unit, so a reading hovering around the threshold doesn't fire over and over
+const HYSTERESIS: f32 = 5.0;
+
+/// A rule, and whether it's firing as of the last check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub rule: Alert,
+    /// The resource's current reading, None if it couldn't be read
+    pub value: Option<f32>,
+    pub firing: bool,
+    /// Whether it started firing on this check, i.e. whether to notify about it
+    pub started: bool,
+}
+
+/// Where a rule is at
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    /// When the resource went past the threshold, None if it's below it
+    since: Option<Instant>,
+    firing: bool,
+}
+
+/// Tracks how long each rule has been past its threshold
+#[derive(Debug, Default)]
+pub struct Alerts {
+    rules: Vec<Alert>,
+    states: Vec<State>,
+}
+
+impl Alerts {
+    /// Checks every rule against the reading `read` gives for it
+    pub fn check(&mut self, rules: &[Alert], read: impl Fn(&Alert) -> Option<f32>) -> Vec<Check> {
+        self.check_at(Instant::now(), rules, read)
+    }
+
+    fn check_at(
+        &mut self,
+        now: Instant,
+        rules: &[Alert],
+        read: impl Fn(&Alert) -> Option<f32>,
+    ) -> Vec<Check> {
+        // start over when the config changes, rather than matching old rules to new ones
+        if self.rules != rules {
+            self.rules = rules.to_vec();
+            self.states = vec![State::default(); rules.len()];
+        }
+
+        self.rules
+            .iter()
+            .zip(&mut self.states)
+            .map(|(rule, state)| {
+                let value = read(rule);
+                let above = |threshold: f32| value.is_some_and(|value| value > threshold);
+
+                if state.firing {
+                    if !above(rule.above - HYSTERESIS) {
+                        *state = State::default();
+                    }
+                } else if !above(rule.above) {
+                    state.since = None;
+                } else if state.since.is_none() {
+                    state.since = Some(now);
+                }
+
+                let started = !state.firing
+                    && state.since.is_some_and(|since| {
+                        now.duration_since(since) >= Duration::from_secs(rule.sustain)
+                    });
+                state.firing |= started;
+
+                Check {
+                    rule: rule.clone(),
+                    value,
+                    firing: state.firing,
+                    started,
+                }
+            })
+            .collect()
+    }
+}
+
+/// e.g. `RAM above 90% for 30 s` or `Disk / above 95%`
+pub fn describe(rule: &Alert) -> String {
+    let resource = match (&rule.mount, &rule.sensor) {
+        (Some(mount), _) => format!("{} {}", rule.resource.name(), mount.display()),
+        (_, Some(sensor)) => format!("{} of {sensor}", rule.resource.name()),
+        _ => rule.resource.name().to_string(),
+    };
+
+    let threshold = format_value(rule.resource, rule.above);
+    match rule.sustain {
+        0 => format!("{resource} above {threshold}"),
+        sustain => format!("{resource} above {threshold} for {sustain} s"),
+    }
+}
+
+/// A reading in the rule's unit, e.g. `93%` or `91°C`
+pub fn format_value(resource: Resource, value: f32) -> String {
+    match resource {
+        Resource::Temperature => format!("{value:.0}°C"),
+        _ => format!("{value:.0}%"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(resource: Resource, above: f32, sustain: u64) -> Alert {
+        Alert {
+            resource,
+            above,
+            sustain,
+            mount: None,
+            sensor: None,
+        }
+    }
+
+    /// Checks `rules` once per reading, `seconds` after the first check, returning whether the
+    /// first rule was firing each time
+    fn check_readings(
+        alerts: &mut Alerts,
+        rules: &[Alert],
+        readings: &[(u64, Option<f32>)],
+    ) -> Vec<bool> {
+        let start = Instant::now();
+
+        readings
+            .iter()
+            .map(|&(seconds, value)| {
+                alerts.check_at(start + Duration::from_secs(seconds), rules, |_rule| value)[0]
+                    .firing
+            })
+            .collect()
+    }
+
+    /// How many times the first rule started firing, which is how many notifications would be sent
+    fn notifications(alerts: &mut Alerts, rules: &[Alert], readings: &[Option<f32>]) -> usize {
+        let start = Instant::now();
+
+        readings
+            .iter()
+            .enumerate()
+            .filter(|&(tick, &value)| {
+                let now = start + Duration::from_secs(tick as u64 * 5);
+                alerts.check_at(now, rules, |_rule| value)[0].started
+            })
+            .count()
+    }
+
+    #[test]
+    fn fires_once_the_threshold_is_crossed() {
+        let rules = [rule(Resource::Ram, 90.0, 0)];
+        let firing = check_readings(
+            &mut Alerts::default(),
+            &rules,
+            &[(0, Some(50.0)), (5, Some(90.0)), (10, Some(95.0))],
+        );
+
+        assert_eq!(firing, [false, false, true]);
+    }
+
+    #[test]
+    fn waits_for_the_reading_to_stay_above_for_long_enough() {
+        let rules = [rule(Resource::Cpu, 80.0, 30)];
+        let firing = check_readings(
+            &mut Alerts::default(),
+            &rules,
+            &[
+                (0, Some(95.0)),
+                (10, Some(95.0)),
+                (29, Some(95.0)),
+                (30, Some(95.0)),
+            ],
+        );
+
+        assert_eq!(firing, [false, false, false, true]);
+    }
+
+    #[test]
+    fn stays_firing_while_above_without_starting_again() {
+        let rules = [rule(Resource::Swap, 50.0, 10)];
+        let readings: Vec<_> = (0..10).map(|tick| (tick * 5, Some(75.0))).collect();
+        let firing = check_readings(&mut Alerts::default(), &rules, &readings);
+
+        assert_eq!(firing[..2], [false, false]);
+        assert!(firing[2..].iter().all(|&firing| firing));
+        assert_eq!(
+            notifications(&mut Alerts::default(), &rules, &[Some(75.0); 10]),
+            1
+        );
+    }
+
+    #[test]
+    fn stops_firing_and_starts_the_wait_over_once_below() {
+        let rules = [rule(Resource::Temperature, 90.0, 10)];
+        let firing = check_readings(
+            &mut Alerts::default(),
+            &rules,
+            &[
+                (0, Some(95.0)),
+                (10, Some(95.0)),
+                (15, Some(60.0)),
+                (20, Some(95.0)),
+                (25, Some(95.0)),
+                (30, Some(95.0)),
+            ],
+        );
+
+        assert_eq!(firing, [false, true, false, false, false, true]);
+        assert_eq!(
+            notifications(
+                &mut Alerts::default(),
+                &rules,
+                &[
+                    Some(95.0),
+                    Some(95.0),
+                    Some(95.0),
+                    Some(60.0),
+                    Some(95.0),
+                    Some(95.0),
+                    Some(95.0)
+                ]
+            ),
+            2
+        );
+    }
+
+    #[test]
+    fn keeps_firing_until_well_below_the_threshold() {
+        let rules = [rule(Resource::Ram, 90.0, 0)];
+        let firing = check_readings(
+            &mut Alerts::default(),
+            &rules,
+            &[
+                (0, Some(95.0)),
+                (5, Some(88.0)),
+                (10, Some(85.5)),
+                (15, Some(85.0)),
+                (20, Some(88.0)),
+            ],
+        );
+
+        assert_eq!(firing, [true, true, true, false, false]);
+    }
+
+    #[test]
+    fn notifies_once_while_hovering_around_the_threshold() {
+        let rules = [rule(Resource::Cpu, 90.0, 0)];
+        let readings = [91.0, 89.0, 91.0, 88.0, 92.0, 90.0, 91.0].map(Some);
+
+        assert_eq!(notifications(&mut Alerts::default(), &rules, &readings), 1);
+    }
+
+    #[test]
+    fn treats_missing_readings_as_below() {
+        let rules = [rule(Resource::Disk, 90.0, 0)];
+        let firing = check_readings(
+            &mut Alerts::default(),
+            &rules,
+            &[(0, Some(95.0)), (5, None), (10, Some(95.0))],
+        );
+
+        assert_eq!(firing, [true, false, true]);
+    }
+
+    #[test]
+    fn starts_over_when_the_rules_change() {
+        let mut alerts = Alerts::default();
+        let before = [rule(Resource::Ram, 90.0, 10)];
+        let after = [rule(Resource::Ram, 80.0, 10)];
+
+        assert_eq!(
+            check_readings(&mut alerts, &before, &[(0, Some(95.0)), (10, Some(95.0))]),
+            [false, true]
+        );
+        assert_eq!(
+            check_readings(&mut alerts, &after, &[(0, Some(95.0))]),
+            [false]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Battery status for the top bar.
+//!
+//! Read from UPower when it's running, otherwise from `/sys/class/power_supply`.
+
+use std::{collections::HashMap, fs, path::Path, time::Duration};
+
+use gtk::{gio, glib, prelude::*};
+use tracing::debug;
+
+const UPOWER_BUS_NAME: &str = "org.freedesktop.UPower";
+/// A single device combining every battery, as shown in most desktops
+const DISPLAY_DEVICE: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+/// How many points the battery has to charge back past a threshold before it's warned about again
+const REARM_MARGIN: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Charging,
+    Discharging,
+    Full,
+    /// Plugged in, but not charging, e.g. because of a charge limit
+    NotCharging,
+}
+
+#[derive(Debug, Clone)]
+pub struct Battery {
+    /// From 0 to 100
+    pub percentage: f64,
+    pub state: State,
+    /// Until empty when discharging, or until full when charging. None if it isn't known yet.
+    pub time_left: Option<Duration>,
+}
+
+impl Battery {
+    /// Reads the battery, or None if there isn't one
+    pub async fn read(upower: bool) -> Option<Self> {
+        if upower {
+            match from_upower().await {
+                Ok(battery) => return battery,
+                Err(err) => debug!(%err, "Could not read the battery from UPower"),
+            }
+        }
+
+        from_sysfs()
+    }
+
+    pub fn icon(&self) -> String {
+        if self.state == State::Full {
+            return "battery-level-100-charged-symbolic".to_string();
+        }
+
+        let level = (self.percentage / 10.0).floor().clamp(0.0, 10.0) as u32 * 10;
+        match self.state {
+            State::Charging => format!("battery-level-{level}-charging-symbolic"),
+            _ => format!("battery-level-{level}-symbolic"),
+        }
+    }
+
+    /// e.g. `Discharging · 2 h 15 min left`
+    pub fn description(&self) -> String {
+        let state = match self.state {
+            State::Charging => "Charging",
+            State::Discharging => "Discharging",
+            State::Full => "Fully charged",
+            State::NotCharging => "Plugged in, not charging",
+        };
+
+        let Some(time_left) = self.time_left.filter(|time| !time.is_zero()) else {
+            return state.to_string();
+        };
+
+        let minutes = time_left.as_secs() / 60;
+        let time = match (minutes / 60, minutes % 60) {
+            (0, minutes) => format!("{minutes} min"),
+            (hours, minutes) => format!("{hours} h {minutes} min"),
+        };
+
+        match self.state {
+            State::Charging => format!("{state} · {time} until full"),
+            _ => format!("{state} · {time} left"),
+        }
+    }
+}
+
+/// How low a discharging battery is, against the thresholds in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    pub fn of(battery: &Battery, warning: u8, critical: u8) -> Self {
+        if battery.state != State::Discharging {
+            Self::Normal
+        } else if battery.percentage <= f64::from(critical) {
+            Self::Critical
+        } else if battery.percentage <= f64::from(warning) {
+            Self::Warning
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// Decides when to warn about a low battery. A level is only warned about again once the battery
+/// has charged back past its threshold by a margin, so a reading that hovers around the threshold,
+/// or a charger that's plugged in for a moment, doesn't warn every time it crosses it.
+#[derive(Debug, Default)]
+pub struct Warnings {
+    /// The lowest level warned about since the battery last recovered
+    warned: Level,
+}
+
+impl Warnings {
+    /// Returns the level to warn about, if the battery has just reached it
+    pub fn update(&mut self, battery: &Battery, warning: u8, critical: u8) -> Option<Level> {
+        let recovered = |threshold: u8| battery.percentage > f64::from(threshold) + REARM_MARGIN;
+
+        if self.warned == Level::Critical && recovered(critical) {
+            self.warned = Level::Warning;
+        }
+        if self.warned == Level::Warning && recovered(warning) {
+            self.warned = Level::Normal;
+        }
+
+        let level = Level::of(battery, warning, critical);
+        if level <= self.warned {
+            return None;
+        }
+
+        self.warned = level;
+        Some(level)
+    }
+}
+
+async fn from_upower() -> Result<Option<Battery>, glib::Error> {
+    let bus = gio::bus_get_future(gio::BusType::System).await?;
+    let reply = bus
+        .call_future(
+            Some(UPOWER_BUS_NAME),
+            DISPLAY_DEVICE,
+            "org.freedesktop.DBus.Properties",
+            "GetAll",
+            Some(&("org.freedesktop.UPower.Device",).to_variant()),
+            glib::VariantTy::new("(a{sv})").ok(),
+            gio::DBusCallFlags::NONE,
+            1000,
+        )
+        .await?;
+
+    let properties: HashMap<String, glib::Variant> = reply.child_value(0).get().unwrap_or_default();
+    let property = |name: &str| properties.get(name);
+
+    // UP_DEVICE_KIND_BATTERY
+    let is_battery = property("Type").and_then(|kind| kind.get::<u32>()) == Some(2);
+    let is_present = property("IsPresent").and_then(|present| present.get::<bool>());
+    if !is_battery || is_present != Some(true) {
+        return Ok(None);
+    }
+
+    // UpDeviceState
+    let state = match property("State").and_then(|state| state.get::<u32>()) {
+        Some(1 | 5) => State::Charging,
+        Some(4) => State::Full,
+        Some(2 | 3 | 6) => State::Discharging,
+        _ => State::NotCharging,
+    };
+    let seconds = |name| {
+        property(name)
+            .and_then(|seconds| seconds.get::<i64>())
+            .filter(|seconds| *seconds > 0)
+            .map(|seconds| Duration::from_secs(seconds as u64))
+    };
+
+    Ok(Some(Battery {
+        percentage: property("Percentage")
+            .and_then(|percentage| percentage.get::<f64>())
+            .unwrap_or_default(),
+        time_left: match state {
+            State::Charging => seconds("TimeToFull"),
+            State::Discharging => seconds("TimeToEmpty"),
+            _ => None,
+        },
+        state,
+    }))
+}
+
+/// The first battery the kernel knows about
+fn from_sysfs() -> Option<Battery> {
+    let battery = fs::read_dir(POWER_SUPPLY)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| read(path, "type").as_deref() == Some("Battery"))?;
+
+    let state = match read(&battery, "status").as_deref() {
+        Some("Charging") => State::Charging,
+        Some("Discharging") => State::Discharging,
+        Some("Full") => State::Full,
+        _ => State::NotCharging,
+    };
+
+    let number = |name| read(&battery, name).and_then(|value| value.parse::<f64>().ok());
+    // batteries report either energy (µWh) and power (µW), or charge (µAh) and current (µA)
+    let (now, full, rate) = match number("energy_now") {
+        Some(now) => (Some(now), number("energy_full"), number("power_now")),
+        None => (
+            number("charge_now"),
+            number("charge_full"),
+            number("current_now"),
+        ),
+    };
+
+    let hours = match (state, now, full, rate.filter(|rate| *rate > 0.0)) {
+        (State::Discharging, Some(now), _, Some(rate)) => Some(now / rate),
+        (State::Charging, Some(now), Some(full), Some(rate)) => Some((full - now).max(0.0) / rate),
+        _ => None,
+    };
+
+    Some(Battery {
+        percentage: number("capacity").unwrap_or_default(),
+        state,
+        time_left: hours.map(|hours| Duration::from_secs_f64(hours * 60.0 * 60.0)),
+    })
+}
+
+fn read(device: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(device.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discharging(percentage: f64) -> Battery {
+        Battery {
+            percentage,
+            state: State::Discharging,
+            time_left: None,
+        }
+    }
+
+    fn charging(percentage: f64) -> Battery {
+        Battery {
+            state: State::Charging,
+            ..discharging(percentage)
+        }
+    }
+
+    /// Updates the warnings once per reading, returning what was warned about each time
+    fn warn(readings: &[Battery]) -> Vec<Option<Level>> {
+        let mut warnings = Warnings::default();
+        readings
+            .iter()
+            .map(|battery| warnings.update(battery, 20, 5))
+            .collect()
+    }
+
+    #[test]
+    fn warns_once_per_level() {
+        let warned = warn(&[
+            discharging(25.0),
+            discharging(20.0),
+            discharging(19.0),
+            discharging(5.0),
+            discharging(4.0),
+        ]);
+
+        assert_eq!(
+            warned,
+            [
+                None,
+                Some(Level::Warning),
+                None,
+                Some(Level::Critical),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_warn_again_while_hovering_around_the_threshold() {
+        let warned = warn(&[
+            discharging(20.0),
+            discharging(21.0),
+            discharging(20.0),
+            charging(21.0),
+            discharging(20.0),
+        ]);
+
+        assert_eq!(warned, [Some(Level::Warning), None, None, None, None]);
+    }
+
+    #[test]
+    fn warns_again_once_charged_past_the_margin() {
+        let warned = warn(&[
+            discharging(4.0),
+            charging(11.0),
+            discharging(5.0),
+            charging(26.0),
+            discharging(20.0),
+        ]);
+
+        assert_eq!(
+            warned,
+            [
+                Some(Level::Critical),
+                None,
+                Some(Level::Critical),
+                None,
+                Some(Level::Warning)
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_warning_when_already_critical() {
+        let warned = warn(&[discharging(50.0), discharging(3.0), discharging(15.0)]);
+
+        assert_eq!(warned, [None, Some(Level::Critical), None]);
+    }
+}
@@ -0,0 +1,138 @@
+//! A rolling hour of CPU and memory usage, and the graphs drawn from it.
+
+use std::{collections::VecDeque, time::Duration};
+
+use gtk::{cairo, gdk};
+
+/// How often usage is sampled
+pub const INTERVAL: Duration = Duration::from_secs(5);
+/// An hour of samples
+pub const CAPACITY: usize = 60 * 60 / INTERVAL.as_secs() as usize;
+/// Five minutes of samples, for the sparklines in the bar
+pub const RECENT: usize = 5 * 60 / INTERVAL.as_secs() as usize;
+
+/// Usage at a single point in time, each from 0 to 1
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub cpu: f32,
+    pub cores: Vec<f32>,
+    pub ram: f32,
+    pub swap: f32,
+}
+
+#[derive(Debug)]
+pub struct History {
+    samples: VecDeque<Sample>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(CAPACITY),
+        }
+    }
+}
+
+impl History {
+    /// Adds the latest sample, dropping the oldest once there's an hour of them
+    pub fn push(&mut self, sample: Sample) {
+        if self.samples.len() == CAPACITY {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    /// The last `count` values of a series, oldest first
+    pub fn series(&self, count: usize, value: impl Fn(&Sample) -> f32) -> Vec<f32> {
+        self.samples
+            .iter()
+            .skip(self.samples.len().saturating_sub(count))
+            .map(value)
+            .collect()
+    }
+
+    /// The number of cores in the latest sample
+    pub fn cores(&self) -> usize {
+        self.samples.back().map_or(0, |sample| sample.cores.len())
+    }
+
+    /// The highest value of a series over the whole history
+    pub fn peak(&self, value: impl Fn(&Sample) -> f32) -> f32 {
+        self.samples.iter().map(value).fold(0.0, f32::max)
+    }
+}
+
+/// How a series is drawn
+#[derive(Debug, Clone, Copy)]
+pub enum Line {
+    /// A solid line with the area under it shaded
+    Filled,
+    Dashed,
+    /// A thin, see-through line, for when there are many of them
+    Faint,
+}
+
+/// Picks the series a graph shows, given how many samples fit across it
+pub type Series = fn(&History, usize) -> Vec<(Vec<f32>, Line)>;
+
+/// Draws `values` across an area `width` by `height` in `color`.
+///
+/// `slots` is how many values fit across, so a history that hasn't filled up yet is drawn against
+/// the right edge, where the latest value is.
+pub fn draw(
+    cr: &cairo::Context,
+    width: f64,
+    height: f64,
+    color: &gdk::RGBA,
+    values: &[f32],
+    slots: usize,
+    line: Line,
+) -> Result<(), cairo::Error> {
+    if values.len() < 2 {
+        return Ok(());
+    }
+
+    let step = width / (slots.max(2) - 1) as f64;
+    let start = width - step * (values.len() - 1) as f64;
+    let y = |value: f32| height - f64::from(value.clamp(0.0, 1.0)) * height;
+
+    cr.save()?;
+    cr.new_path();
+    cr.move_to(start, y(values[0]));
+    for (i, value) in values.iter().enumerate().skip(1) {
+        cr.line_to(start + step * i as f64, y(*value));
+    }
+
+    let alpha = f64::from(color.alpha());
+    let (red, green, blue) = (
+        f64::from(color.red()),
+        f64::from(color.green()),
+        f64::from(color.blue()),
+    );
+
+    match line {
+        Line::Filled => cr.set_line_width(2.0),
+        Line::Dashed => {
+            cr.set_line_width(1.5);
+            cr.set_dash(&[4.0, 3.0], 0.0);
+        }
+        Line::Faint => cr.set_line_width(1.0),
+    }
+
+    let line_alpha = if let Line::Faint = line { 0.3 } else { 1.0 };
+    cr.set_source_rgba(red, green, blue, alpha * line_alpha);
+
+    if let Line::Filled = line {
+        cr.stroke_preserve()?;
+        cr.line_to(width, height);
+        cr.line_to(start, height);
+        cr.close_path();
+        cr.set_source_rgba(red, green, blue, alpha * 0.25);
+        cr.fill()?;
+    } else {
+        cr.stroke()?;
+    }
+
+    cr.restore()
+}
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use adw::subclass::window::AdwWindowImpl;
 use glib::subclass::InitializingObject;
@@ -7,8 +7,16 @@ use gtk::glib::Properties;
 use gtk::subclass::prelude::*;
 use gtk::{gio, prelude::*};
 use gtk::{glib, CompositeTemplate};
+use tracing::error;
 
-use super::weather;
+use super::{
+    history::History,
+    processes::{Process, Signal},
+    storage::Storage,
+    weather,
+    wifi::{self, AccessPoint, Wifi},
+};
+use crate::autohide::AutoHide;
 
 #[derive(CompositeTemplate, Properties, Default, Debug)]
 #[template(resource = "/in/wobbl/commashell/ui/top.ui")]
@@ -20,10 +28,18 @@ pub struct Top {
     pub time: RefCell<String>,
     #[property(get, set)]
     pub reveal: RefCell<bool>,
+    #[template_child]
+    pub calendar: TemplateChild<gtk::Calendar>,
+    /// The time in each of the configured time zones
+    #[template_child]
+    pub world_clocks: TemplateChild<gtk::ListBox>,
     #[property(get, set)]
     pub use_metric_units: RefCell<bool>,
     #[property(get, set)]
     pub wallpaper_command: RefCell<String>,
+    /// Path of the last wallpaper picked, empty if none has been
+    #[property(get, set)]
+    pub wallpaper: RefCell<String>,
 
     #[property(get, set)]
     pub weather_temp: RefCell<f32>,
@@ -47,7 +63,19 @@ pub struct Top {
     pub humidity: RefCell<String>,
     #[property(get, set)]
     pub uv: RefCell<String>,
+    #[property(get, set)]
+    pub humidity_value: RefCell<u32>,
+    #[property(get, set)]
+    pub cloud_cover_value: RefCell<u32>,
+    #[property(get, set)]
+    pub uv_value: RefCell<u32>,
+    /// Unix timestamp of the last successful weather fetch, 0 if there hasn't been one
+    #[property(get, set)]
+    pub weather_updated: RefCell<i64>,
 
+    #[template_child]
+    pub revealer: TemplateChild<gtk::Revealer>,
+    pub auto_hide: RefCell<Option<AutoHide>>,
     #[template_child]
     pub wallpaper_items: TemplateChild<gtk::ListBox>,
     #[template_child]
@@ -83,14 +111,129 @@ pub struct Top {
     #[template_child]
     pub wallpaper_click: TemplateChild<gtk::GestureClick>,
 
-    #[property(get, set)]
-    pub cpu_usage_value: RefCell<f32>,
     #[property(get, set)]
     pub cpu_usage: RefCell<String>,
     #[property(get, set)]
-    pub ram_usage_value: RefCell<f32>,
-    #[property(get, set)]
     pub ram_usage: RefCell<String>,
+    /// Headings for the charts in the usage popover
+    #[property(get, set)]
+    pub cpu_history: RefCell<String>,
+    #[property(get, set)]
+    pub memory_history: RefCell<String>,
+    pub history: RefCell<History>,
+    /// Its popover's process lists are left alone while it's open
+    #[template_child]
+    pub usage_button: TemplateChild<gtk::MenuButton>,
+    /// The CPU and RAM meters in the bar, marked when an alert fires
+    #[template_child]
+    pub cpu_meter: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub ram_meter: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub processes_by_cpu: TemplateChild<gtk::ListBox>,
+    #[template_child]
+    pub processes_by_memory: TemplateChild<gtk::ListBox>,
+    /// Asks before killing a process or signalling someone else's
+    #[property(get, set)]
+    pub process_confirmation: RefCell<String>,
+    /// The confirmation's button, e.g. `Kill`
+    #[property(get, set)]
+    pub process_action: RefCell<String>,
+    #[template_child]
+    pub process_confirmation_revealer: TemplateChild<gtk::Revealer>,
+    /// The process and signal waiting on confirmation
+    pub process_pending: RefCell<Option<(Process, Signal)>>,
+    /// Errors from signalling processes
+    #[property(get, set)]
+    pub process_status: RefCell<String>,
+
+    #[property(get, set)]
+    pub network_icon: RefCell<String>,
+    /// The primary connection's name
+    #[property(get, set)]
+    pub network_name: RefCell<String>,
+    /// Throughput across every interface
+    #[property(get, set)]
+    pub network_speed: RefCell<String>,
+    #[property(get, set)]
+    pub network_details: RefCell<String>,
+    #[template_child]
+    pub network_button: TemplateChild<gtk::MenuButton>,
+    #[template_child]
+    pub network_interfaces: TemplateChild<gtk::ListBox>,
+
+    /// The chosen sensor's temperature
+    #[property(get, set)]
+    pub temperature: RefCell<String>,
+    #[property(get, set)]
+    pub temperature_sensor: RefCell<String>,
+    #[template_child]
+    pub sensors_button: TemplateChild<gtk::MenuButton>,
+    #[template_child]
+    pub sensors: TemplateChild<gtk::ListBox>,
+
+    /// How full the first configured mount is
+    #[property(get, set)]
+    pub storage_usage: RefCell<String>,
+    /// Progress and errors in the storage popover
+    #[property(get, set)]
+    pub storage_status: RefCell<String>,
+    #[template_child]
+    pub storage_button: TemplateChild<gtk::MenuButton>,
+    #[template_child]
+    pub storage_mounts: TemplateChild<gtk::ListBox>,
+    #[template_child]
+    pub storage_removable: TemplateChild<gtk::Image>,
+    #[template_child]
+    pub storage_drives_box: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub storage_drives: TemplateChild<gtk::ListBox>,
+    /// The UDisks2 client, if it's available
+    pub storage: RefCell<Option<Storage>>,
+    /// UDisks2's change signals, unsubscribed when the window goes away
+    pub storage_watch: RefCell<Vec<gio::SignalSubscriptionId>>,
+    /// Drives are listed again once changes settle down
+    pub storage_refresh: RefCell<Option<glib::SourceId>>,
+
+    #[property(get, set)]
+    pub battery_icon: RefCell<String>,
+    #[property(get, set)]
+    pub battery_percentage: RefCell<String>,
+    /// Charging state and time left
+    #[property(get, set)]
+    pub battery_description: RefCell<String>,
+    #[template_child]
+    pub battery: TemplateChild<gtk::Box>,
+
+    /// Progress and errors in the Wi-Fi popover
+    #[property(get, set)]
+    pub wifi_status: RefCell<String>,
+    #[template_child]
+    pub wifi_button: TemplateChild<gtk::MenuButton>,
+    #[template_child]
+    pub wifi_switch: TemplateChild<gtk::Switch>,
+    #[template_child]
+    pub wifi_networks: TemplateChild<gtk::ListBox>,
+    #[template_child]
+    pub wifi_passphrase_revealer: TemplateChild<gtk::Revealer>,
+    #[template_child]
+    pub wifi_passphrase: TemplateChild<gtk::PasswordEntry>,
+    pub wifi: RefCell<Option<Wifi>>,
+    /// The networks listed, in the same order as their rows
+    pub access_points: RefCell<Vec<AccessPoint>>,
+    /// The network the passphrase being typed is for
+    pub wifi_pending: RefCell<Option<AccessPoint>>,
+    /// Set while the switch is moved to match NetworkManager, rather than by the user
+    pub wifi_syncing: Cell<bool>,
+
+    #[template_child]
+    pub cpu_sparkline: TemplateChild<gtk::DrawingArea>,
+    #[template_child]
+    pub ram_sparkline: TemplateChild<gtk::DrawingArea>,
+    #[template_child]
+    pub cpu_chart: TemplateChild<gtk::DrawingArea>,
+    #[template_child]
+    pub memory_chart: TemplateChild<gtk::DrawingArea>,
 
     #[property(get, set)]
     pub power_menu_visible: RefCell<bool>,
@@ -128,7 +271,7 @@ impl Top {
                         }
                     }
                     Err(err) => {
-                        eprintln!("Error selecting folder: {}", err);
+                        error!(%err, "Could not select wallpaper folder");
                     }
                 }
             }
@@ -203,6 +346,123 @@ impl Top {
         false
     }
 
+    #[template_callback]
+    pub fn on_wifi_opened(&self) {
+        let obj = self.obj();
+
+        glib::spawn_future_local(glib::clone!(
+            #[weak]
+            obj,
+            async move {
+                obj.refresh_wifi(true).await;
+            }
+        ));
+    }
+
+    #[template_callback]
+    pub fn on_wifi_toggled(&self, enabled: bool) -> bool {
+        if self.wifi_syncing.get() {
+            return false;
+        }
+
+        let obj = self.obj();
+        glib::spawn_future_local(glib::clone!(
+            #[weak]
+            obj,
+            async move {
+                obj.set_wifi_enabled(enabled).await;
+            }
+        ));
+
+        false
+    }
+
+    #[template_callback]
+    pub fn on_wifi_network_activated(&self, row: gtk::ListBoxRow) {
+        let Some(network) = self
+            .access_points
+            .borrow()
+            .get(row.index() as usize)
+            .cloned()
+        else {
+            return;
+        };
+
+        let obj = self.obj();
+        if !network.supported() {
+            obj.set_wifi_status(format!(
+                "Could not connect to {}: {}",
+                network.ssid,
+                wifi::Error::Unsupported(network.security)
+            ));
+            return;
+        }
+
+        if network.needs_passphrase() {
+            obj.set_wifi_status(format!("Enter the passphrase for {}", network.ssid));
+            self.wifi_pending.replace(Some(network));
+            self.wifi_passphrase_revealer.set_reveal_child(true);
+            self.wifi_passphrase.grab_focus();
+            return;
+        }
+
+        glib::spawn_future_local(glib::clone!(
+            #[weak]
+            obj,
+            async move {
+                obj.connect_wifi(network, None).await;
+            }
+        ));
+    }
+
+    #[template_callback]
+    pub fn on_wifi_connect(&self) {
+        let Some(network) = self.wifi_pending.take() else {
+            return;
+        };
+
+        let passphrase = self.wifi_passphrase.text().to_string();
+        self.wifi_passphrase.set_text("");
+        self.wifi_passphrase_revealer.set_reveal_child(false);
+
+        let obj = self.obj();
+        glib::spawn_future_local(glib::clone!(
+            #[weak]
+            obj,
+            async move {
+                obj.connect_wifi(network, Some(passphrase)).await;
+            }
+        ));
+    }
+
+    #[template_callback]
+    pub fn on_clock_opened(&self) {
+        if let Ok(now) = glib::DateTime::now_local() {
+            self.calendar.select_day(&now);
+        }
+
+        self.obj().update_world_clocks();
+    }
+
+    #[template_callback]
+    pub fn on_process_confirmed(&self) {
+        self.process_confirmation_revealer.set_reveal_child(false);
+        if let Some((process, signal)) = self.process_pending.take() {
+            self.obj().send_signal(&process, signal);
+        }
+    }
+
+    #[template_callback]
+    pub fn on_usage_closed(&self) {
+        self.on_process_cancelled();
+    }
+
+    #[template_callback]
+    pub fn on_process_cancelled(&self) {
+        self.process_confirmation_revealer.set_reveal_child(false);
+        self.process_pending.take();
+    }
+
     #[template_callback]
     pub fn on_power_menu(&self) {
         let obj = self.obj();
@@ -214,7 +474,7 @@ impl Top {
         let obj = self.obj();
         obj.set_power_menu_visible(false);
         if let Err(e) = system_shutdown::sleep() {
-            eprintln!("Error sleeping: {}", e);
+            error!(err = %e, "Could not sleep");
         }
     }
 
@@ -223,7 +483,7 @@ impl Top {
         let obj = self.obj();
         obj.set_power_menu_visible(false);
         if let Err(e) = system_shutdown::shutdown() {
-            eprintln!("Error @shutting down: {}", e);
+            error!(err = %e, "Could not shut down");
         }
     }
 
@@ -232,7 +492,7 @@ impl Top {
         let obj = self.obj();
         obj.set_power_menu_visible(false);
         if let Err(e) = system_shutdown::reboot() {
-            eprintln!("Error restarting: {}", e);
+            error!(err = %e, "Could not restart");
         }
     }
 
@@ -241,7 +501,7 @@ impl Top {
         let obj = self.obj();
         obj.set_power_menu_visible(false);
         if let Err(e) = system_shutdown::logout() {
-            eprintln!("Error logging out: {}", e);
+            error!(err = %e, "Could not log out");
         }
     }
 }
@@ -279,6 +539,18 @@ impl ObjectImpl for Top {
         self.wallpaper_right_click
             .set_parent(&self.wallpaper_button.get());
     }
+
+    fn dispose(&self) {
+        // the system bus outlives the window, and would keep calling back into it
+        let watches = self.storage_watch.take();
+        if let Some(storage) = self.storage.borrow().as_ref() {
+            storage.unwatch(watches);
+        }
+
+        if let Some(source) = self.storage_refresh.take() {
+            source.remove();
+        }
+    }
 }
 
 impl AdwWindowImpl for Top {}
@@ -1,21 +1,43 @@
-use std::{path::PathBuf, process::Command};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
 
+use adw::prelude::{ActionRowExt, ExpanderRowExt};
 use futures_util::StreamExt;
 use gtk::{
     gdk::Monitor,
-    gio::{self, prelude::*, Settings, SettingsBindFlags},
+    gio::{self, prelude::*, SettingsBindFlags},
     glib::{self, Object},
     prelude::*,
     subclass::prelude::*,
 };
-use gtk4_layer_shell::{Edge, LayerShell};
-use sysinfo::System;
+use gtk4_layer_shell::LayerShell;
+use sysinfo::{System, Users};
+use tracing::{debug, error, warn};
 use wallpaper::WallpaperEntryObject;
+use wifi::Wifi;
+
+use crate::{
+    app::App,
+    autohide::AutoHide,
+    notify, placement,
+    services::Service,
+    user_config::{Edge, Resource},
+    TOKIO_RUNTIME,
+};
 
-use crate::{app::App, config::APP_ID, TOKIO_RUNTIME};
-
+mod alerts;
+mod battery;
+mod history;
 mod imp;
+mod network;
+mod processes;
+mod sensors;
+mod storage;
 mod weather;
+mod wifi;
 
 glib::wrapper! {
     pub struct Top(ObjectSubclass<imp::Top>)
@@ -33,12 +55,7 @@ impl Top {
 
         current.set_monitor(monitor);
 
-        current
-            .bind_property("reveal", app, "top-reveal")
-            .bidirectional()
-            .build();
-
-        let settings = Settings::new(APP_ID);
+        let settings = app.settings();
         settings
             .bind("wallpaper-folder", &current, "wallpaper-folder")
             .flags(SettingsBindFlags::GET | SettingsBindFlags::SET)
@@ -65,74 +82,98 @@ impl Top {
             .wallpaper_command_entry
             .set_text(&current.wallpaper_command());
 
-        current.init_layer_shell();
-        let anchors = [
-            (Edge::Left, true),
-            (Edge::Right, true),
-            (Edge::Top, true),
-            (Edge::Bottom, false),
-        ];
+        let layout = &app.config().components.top;
+        let edge = placement::dock(&current, layout, Edge::Top, true);
+        current
+            .imp()
+            .revealer
+            .set_transition_type(if edge == Edge::Bottom {
+                gtk::RevealerTransitionType::SlideUp
+            } else {
+                gtk::RevealerTransitionType::SlideDown
+            });
 
-        for (anchor, state) in anchors {
-            current.set_anchor(anchor, state);
+        if layout.auto_hide {
+            current.imp().auto_hide.replace(Some(AutoHide::attach(
+                &current,
+                edge,
+                Duration::from_millis(layout.hide_delay),
+                Duration::from_millis(layout.peek_duration),
+            )));
         }
 
         current.set_default_height(1);
         current.set_height_request(1);
         current.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
-        current.auto_exclusive_zone_enable();
 
+        current.setup_usage_graphs();
         current.present();
 
-        // update system stats
         glib::spawn_future_local(glib::clone!(
             #[weak]
             current,
             async move {
-                let mut sys = System::new_all();
-                // every 5 seconds
-                let mut stream = glib::interval_stream(std::time::Duration::from_secs(5));
-                sys.refresh_all();
-                std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
-                sys.refresh_cpu_usage();
-
-                current.update_system_stats(&mut sys);
-
-                while (stream.next().await).is_some() {
-                    sys.refresh_all();
-                    current.update_system_stats(&mut sys);
-                }
+                current.setup_wifi().await;
             }
         ));
 
-        // update weather
         glib::spawn_future_local(glib::clone!(
             #[weak]
             current,
             async move {
-                // every 1 hour
-                let mut stream = glib::interval_stream(std::time::Duration::from_secs(60 * 60));
+                current.setup_storage().await;
+            }
+        ));
+
+        // the loops below only hold the window while updating it, so closing it ends them
+        // update weather
+        let top = current.downgrade();
+        glib::spawn_future_local(async move {
+            // every 1 hour
+            let mut stream = glib::interval_stream(std::time::Duration::from_secs(60 * 60));
+
+            while let Some(current) = top.upgrade() {
                 current.update_weather().await;
+                drop(current);
 
-                while (stream.next().await).is_some() {
-                    current.update_weather().await;
+                if stream.next().await.is_none() {
+                    break;
                 }
             }
-        ));
+        });
 
-        // update time
+        // update time, every second or on the minute depending on the format
+        let top = current.downgrade();
         glib::spawn_future_local(glib::clone!(
             #[weak]
-            current,
+            app,
             async move {
-                let mut stream = glib::interval_stream(std::time::Duration::from_secs(1));
+                while let Some(current) = top.upgrade() {
+                    let clock = app.config().clock.clone();
+                    let Ok(now) = glib::DateTime::now_local() else {
+                        drop(current);
+                        glib::timeout_future_seconds(1).await;
+                        continue;
+                    };
 
-                while (stream.next().await).is_some() {
-                    if let Ok(time) =
-                        glib::DateTime::now_local().and_then(|dt| dt.format("%H:%M:%S · %A %d/%m"))
-                    {
+                    if let Ok(time) = now.format(&clock.format()) {
                         current.set_time(time);
+                    }
+
+                    let second = if clock.shows_seconds() {
+                        0
+                    } else {
+                        // the next minute
+                        59 - now.second() as u64
                     };
+                    if current.imp().world_clocks.is_mapped() {
+                        current.update_world_clocks();
+                    }
+                    drop(current);
+
+                    let until_next = Duration::from_secs(second)
+                        + Duration::from_micros(1_000_000 - now.microsecond() as u64);
+                    glib::timeout_future(until_next).await;
                 }
             }
         ));
@@ -140,12 +181,91 @@ impl Top {
         current
     }
 
-    fn update_system_stats(&self, sys: &mut System) {
-        self.set_cpu_usage_value(sys.global_cpu_usage());
-        self.set_cpu_usage(format!("{:.0}%", sys.global_cpu_usage()));
+    /// Lists the time in each configured time zone, in the clock's popover
+    fn update_world_clocks(&self) {
+        let Some(app) = self.application().and_downcast::<App>() else {
+            return;
+        };
+
+        let clock = &app.config().clock;
+        let list = &self.imp().world_clocks;
+        list.remove_all();
+        list.set_visible(!clock.zones.is_empty());
 
-        let used_memory = sys.used_memory() as f64 / sys.total_memory() as f64;
-        self.set_ram_usage_value(used_memory as f32);
+        let Ok(local) = glib::DateTime::now_local() else {
+            return;
+        };
+
+        for zone in &clock.zones {
+            let Some(time) = glib::TimeZone::from_identifier(Some(zone.as_str()))
+                .and_then(|time_zone| local.to_timezone(&time_zone).ok())
+            else {
+                continue;
+            };
+
+            // e.g. America/Argentina/Buenos_Aires
+            let city = zone.rsplit('/').next().unwrap_or(zone).replace('_', " ");
+            let offset = (time.utc_offset().as_minutes() - local.utc_offset().as_minutes()) as i32;
+            let day = match time.ymd().cmp(&local.ymd()) {
+                std::cmp::Ordering::Less => Some("Yesterday"),
+                std::cmp::Ordering::Equal => None,
+                std::cmp::Ordering::Greater => Some("Tomorrow"),
+            };
+
+            let row = adw::ActionRow::builder()
+                .title(city)
+                .subtitle(
+                    [Some(format_offset(offset)), day.map(str::to_string)]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" · "),
+                )
+                .build();
+
+            let label = gtk::Label::new(time.format(clock.time_format(false)).ok().as_deref());
+            label.add_css_class("title-4");
+            label.add_css_class("numeric");
+            row.add_suffix(&label);
+
+            list.append(&row);
+        }
+    }
+
+    /// Briefly shows the bar if it auto-hides
+    pub fn peek(&self) {
+        if let Some(auto_hide) = self.imp().auto_hide.borrow().as_ref() {
+            auto_hide.peek();
+        }
+    }
+
+    fn update_system_stats(&self, sys: &System, sample: &history::Sample) {
+        {
+            let mut history = self.imp().history.borrow_mut();
+            history.push(sample.clone());
+
+            self.set_cpu_history(format!(
+                "CPU · peak {:.0}%",
+                history.peak(|sample| sample.cpu) * 100.0
+            ));
+            self.set_memory_history(format!(
+                "Memory · peak {:.0}% · swap peak {:.0}%",
+                history.peak(|sample| sample.ram) * 100.0,
+                history.peak(|sample| sample.swap) * 100.0
+            ));
+        }
+
+        let imp = self.imp();
+        for graph in [
+            &imp.cpu_sparkline,
+            &imp.ram_sparkline,
+            &imp.cpu_chart,
+            &imp.memory_chart,
+        ] {
+            graph.queue_draw();
+        }
+
+        self.set_cpu_usage(format!("{:.0}%", sys.global_cpu_usage()));
         self.set_ram_usage(format!(
             " {:.0}GB / {:.0}GB",
             // bytes to gigabytes
@@ -154,6 +274,787 @@ impl Top {
         ));
     }
 
+    /// Lists the busiest processes in the usage popover
+    fn update_processes(&self, by_cpu: &[processes::Process], by_memory: &[processes::Process]) {
+        let imp = self.imp();
+
+        // the rows would reorder under the pointer, and a click could land on another process
+        if popover_open(&imp.usage_button) {
+            return;
+        }
+
+        for (list, processes) in [
+            (&imp.processes_by_cpu, by_cpu),
+            (&imp.processes_by_memory, by_memory),
+        ] {
+            list.remove_all();
+
+            for process in processes {
+                let row = adw::ActionRow::builder()
+                    .title(process.name.as_str())
+                    .use_markup(false)
+                    .subtitle(
+                        [
+                            Some(format!("PID {}", process.pid)),
+                            process.user.clone(),
+                            Some(format!("{:.0}% CPU", process.cpu)),
+                            Some(glib::format_size(process.memory).to_string()),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" · "),
+                    )
+                    .build();
+
+                for (signal, icon, tooltip) in [
+                    (
+                        processes::Signal::Terminate,
+                        "process-stop-symbolic",
+                        "Terminate",
+                    ),
+                    (processes::Signal::Kill, "window-close-symbolic", "Kill"),
+                ] {
+                    let button = gtk::Button::builder()
+                        .icon_name(icon)
+                        .tooltip_text(tooltip)
+                        .valign(gtk::Align::Center)
+                        .build();
+                    button.add_css_class("flat");
+                    if signal == processes::Signal::Kill {
+                        button.add_css_class("destructive-action");
+                    }
+
+                    // the process as it was listed, so a reused PID isn't signalled
+                    let process = process.clone();
+                    button.connect_clicked(glib::clone!(
+                        #[weak(rename_to = current)]
+                        self,
+                        move |_button| current.request_signal(&process, signal)
+                    ));
+                    row.add_suffix(&button);
+                }
+
+                list.append(&row);
+            }
+        }
+    }
+
+    /// Signals `process`, after asking first if it's being killed or isn't the user's own
+    fn request_signal(&self, process: &processes::Process, signal: processes::Signal) {
+        let imp = self.imp();
+
+        if process.own && signal == processes::Signal::Terminate {
+            imp.process_pending.take();
+            imp.process_confirmation_revealer.set_reveal_child(false);
+            self.send_signal(process, signal);
+            return;
+        }
+
+        let action = match signal {
+            processes::Signal::Terminate => "Terminate",
+            processes::Signal::Kill => "Kill",
+        };
+        self.set_process_confirmation(if process.own {
+            format!(
+                "Kill {} (PID {})? It won't get a chance to save its work.",
+                process.name, process.pid
+            )
+        } else {
+            format!(
+                "{} (PID {}) belongs to {}. {action} it anyway?",
+                process.name,
+                process.pid,
+                process.user.as_deref().unwrap_or("another user"),
+            )
+        });
+        self.set_process_action(action);
+        imp.process_pending.replace(Some((process.clone(), signal)));
+        imp.process_confirmation_revealer.set_reveal_child(true);
+    }
+
+    fn send_signal(&self, process: &processes::Process, signal: processes::Signal) {
+        match process.send(signal) {
+            Ok(()) => {
+                debug!(pid = %process.pid, name = %process.name, ?signal, "Signalled process");
+                self.set_process_status(String::new());
+            }
+            Err(err) => self.set_process_status(format!(
+                "Could not {} {}: {err}",
+                signal.name(),
+                process.name
+            )),
+        }
+    }
+
+    fn update_network(
+        &self,
+        interfaces: &[network::Interface],
+        connection: Option<&network::Connection>,
+    ) {
+        let down: f64 = interfaces.iter().map(|interface| interface.down).sum();
+        let up: f64 = interfaces.iter().map(|interface| interface.up).sum();
+        self.set_network_speed(format!(
+            "↓ {} ↑ {}",
+            network::format_rate(down),
+            network::format_rate(up)
+        ));
+
+        let Some(connection) = connection else {
+            self.set_network_icon("network-offline-symbolic");
+            self.set_network_name("Offline");
+            self.set_network_details("Not connected");
+            self.update_network_interfaces(interfaces);
+            return;
+        };
+
+        let addresses = interfaces
+            .iter()
+            .find(|interface| connection.interface.as_ref() == Some(&interface.name))
+            .map(|interface| interface.addresses.join(", "))
+            .filter(|addresses| !addresses.is_empty());
+
+        self.set_network_icon(connection.kind.icon());
+        self.set_network_name(connection.name.as_str());
+        self.set_network_details(
+            [
+                Some(connection.kind.name().to_string()),
+                connection.interface.clone(),
+                addresses,
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" · "),
+        );
+        self.update_network_interfaces(interfaces);
+    }
+
+    /// Lists every interface in the network popover
+    fn update_network_interfaces(&self, interfaces: &[network::Interface]) {
+        let imp = self.imp();
+
+        // rebuilding the list would drop its scroll position and focus while it's being read
+        if popover_open(&imp.network_button) {
+            return;
+        }
+
+        let list = &imp.network_interfaces;
+        list.remove_all();
+
+        for interface in interfaces {
+            let row = adw::ActionRow::builder()
+                .title(format!("{} · {}", interface.name, interface.kind.name()))
+                .subtitle(if interface.addresses.is_empty() {
+                    "No addresses".to_string()
+                } else {
+                    interface.addresses.join("\n")
+                })
+                .build();
+
+            let speed = gtk::Label::new(Some(&format!(
+                "↓ {}\n↑ {}",
+                network::format_rate(interface.down),
+                network::format_rate(interface.up)
+            )));
+            speed.add_css_class("numeric");
+            speed.add_css_class("caption");
+            row.add_prefix(&gtk::Image::from_icon_name(interface.kind.icon()));
+            row.add_suffix(&speed);
+
+            list.append(&row);
+        }
+    }
+
+    fn update_sensors(&self, readings: &[sensors::Reading]) {
+        let Some(app) = self.application().and_downcast::<App>() else {
+            return;
+        };
+
+        let config = app.config().sensors.clone();
+        let unit = self.temperature_unit();
+        let imp = self.imp();
+
+        let chosen = sensors::chosen(readings, config.sensor.as_deref());
+        imp.sensors_button.set_visible(chosen.is_some());
+
+        if let Some(reading) = chosen {
+            self.set_temperature(
+                reading
+                    .temperature
+                    .map(|temperature| sensors::format_temperature(temperature, unit))
+                    .unwrap_or_default(),
+            );
+            self.set_temperature_sensor(reading.label.as_str());
+
+            if reading.is_hot(config.warning) {
+                imp.sensors_button.add_css_class("warning");
+            } else {
+                imp.sensors_button.remove_css_class("warning");
+            }
+        }
+
+        // rebuilding the list would drop its scroll position while it's being read
+        if popover_open(&imp.sensors_button) {
+            return;
+        }
+
+        let list = &imp.sensors;
+        list.remove_all();
+
+        for reading in readings {
+            let limits = [("max", reading.max), ("critical", reading.critical)]
+                .into_iter()
+                .filter_map(|(name, temperature)| {
+                    Some(format!(
+                        "{name} {}",
+                        sensors::format_temperature(temperature?, unit)
+                    ))
+                })
+                .collect::<Vec<_>>()
+                .join(" · ");
+
+            let row = adw::ActionRow::builder()
+                .title(reading.label.as_str())
+                .subtitle(limits)
+                .build();
+
+            let temperature = gtk::Label::new(
+                reading
+                    .temperature
+                    .map(|temperature| sensors::format_temperature(temperature, unit))
+                    .as_deref(),
+            );
+            temperature.add_css_class("numeric");
+            if reading.is_hot(config.warning) {
+                temperature.add_css_class("warning");
+            }
+            row.add_suffix(&temperature);
+
+            list.append(&row);
+        }
+    }
+
+    fn update_storage(&self, sampler: &storage::Sampler) {
+        let Some(app) = self.application().and_downcast::<App>() else {
+            return;
+        };
+
+        let usages: Vec<_> = app
+            .config()
+            .storage
+            .mounts
+            .iter()
+            .filter_map(|mount| sampler.usage(mount))
+            .collect();
+        self.set_storage_usage(
+            usages
+                .first()
+                .map(|usage| format!("{:.0}%", usage.fraction() * 100.0))
+                .unwrap_or_default(),
+        );
+
+        let imp = self.imp();
+
+        // rebuilding the list would drop its scroll position and focus while it's being read
+        if popover_open(&imp.storage_button) {
+            return;
+        }
+
+        let list = &imp.storage_mounts;
+        list.remove_all();
+
+        for usage in &usages {
+            let row = adw::ActionRow::builder()
+                .title(usage.mount_point.display().to_string())
+                .subtitle(format!(
+                    "{} free of {}",
+                    glib::format_size(usage.total - usage.used),
+                    glib::format_size(usage.total)
+                ))
+                .build();
+
+            let bar = gtk::ProgressBar::builder()
+                .fraction(usage.fraction())
+                .valign(gtk::Align::Center)
+                .width_request(96)
+                .build();
+            if usage.fraction() >= 0.9 {
+                bar.add_css_class("warning");
+            }
+            row.add_suffix(&bar);
+
+            list.append(&row);
+        }
+    }
+
+    /// Marks the meters of resources past an alert rule's threshold
+    fn show_alerts(&self, checks: &[alerts::Check]) {
+        let imp = self.imp();
+        let meters: [(&gtk::Widget, &[Resource]); 4] = [
+            (imp.cpu_meter.upcast_ref(), &[Resource::Cpu]),
+            (imp.ram_meter.upcast_ref(), &[Resource::Ram, Resource::Swap]),
+            (imp.storage_button.upcast_ref(), &[Resource::Disk]),
+            (imp.sensors_button.upcast_ref(), &[Resource::Temperature]),
+        ];
+
+        for (meter, resources) in meters {
+            let firing = checks
+                .iter()
+                .any(|check| check.firing && resources.contains(&check.rule.resource));
+
+            if firing {
+                meter.add_css_class("alert");
+            } else {
+                meter.remove_css_class("alert");
+            }
+        }
+    }
+
+    /// Lists removable drives, and again whenever they change, if UDisks2 is available
+    async fn setup_storage(&self) {
+        let udisks = self
+            .application()
+            .and_downcast::<App>()
+            .is_some_and(|app| app.unavailable(Service::UDisks).is_none());
+        if !udisks {
+            return;
+        }
+
+        let storage = match storage::Storage::system().await {
+            Ok(storage) => storage,
+            Err(err) => {
+                debug!(%err, "Not listing removable drives");
+                return;
+            }
+        };
+
+        let watches = storage.watch(glib::clone!(
+            #[weak(rename_to = current)]
+            self,
+            move || current.refresh_drives_later()
+        ));
+        self.imp().storage_watch.replace(watches);
+        self.imp().storage.replace(Some(storage));
+        self.refresh_drives().await;
+    }
+
+    /// Plugging a drive in sends a burst of changes, so they're only listed once it's over
+    fn refresh_drives_later(&self) {
+        if self.imp().storage_refresh.borrow().is_some() {
+            return;
+        }
+
+        let source = glib::timeout_add_local_once(
+            Duration::from_millis(500),
+            glib::clone!(
+                #[weak(rename_to = current)]
+                self,
+                move || {
+                    current.imp().storage_refresh.take();
+                    glib::spawn_future_local(async move {
+                        current.refresh_drives().await;
+                    });
+                }
+            ),
+        );
+
+        self.imp().storage_refresh.replace(Some(source));
+    }
+
+    async fn refresh_drives(&self) {
+        let Some(storage) = self.imp().storage.borrow().clone() else {
+            return;
+        };
+
+        match storage.drives().await {
+            Ok(drives) => self.show_drives(drives),
+            Err(err) => self.set_storage_status(format!("Could not list removable drives: {err}")),
+        }
+    }
+
+    fn show_drives(&self, drives: Vec<storage::Drive>) {
+        let imp = self.imp();
+        imp.storage_removable.set_visible(!drives.is_empty());
+        imp.storage_drives_box.set_visible(!drives.is_empty());
+
+        let list = &imp.storage_drives;
+        list.remove_all();
+
+        for drive in drives {
+            let row = adw::ExpanderRow::builder()
+                .title(drive.name.as_str())
+                .use_markup(false)
+                .subtitle(glib::format_size(drive.size))
+                .expanded(true)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name("media-removable-symbolic"));
+
+            for filesystem in &drive.filesystems {
+                let mount_point = filesystem
+                    .mount_points
+                    .first()
+                    .map_or("Not mounted".to_string(), |point| {
+                        point.display().to_string()
+                    });
+
+                let filesystem_row = adw::ActionRow::builder()
+                    .title(filesystem.name.as_str())
+                    .use_markup(false)
+                    .subtitle(format!(
+                        "{} · {} · {mount_point}",
+                        filesystem.device,
+                        glib::format_size(filesystem.size)
+                    ))
+                    .build();
+
+                let button = gtk::Button::builder()
+                    .label(if filesystem.is_mounted() {
+                        "Unmount"
+                    } else {
+                        "Mount"
+                    })
+                    .valign(gtk::Align::Center)
+                    .build();
+                let filesystem = filesystem.clone();
+                button.connect_clicked(glib::clone!(
+                    #[weak(rename_to = current)]
+                    self,
+                    move |_button| {
+                        let filesystem = filesystem.clone();
+                        glib::spawn_future_local(glib::clone!(
+                            #[weak]
+                            current,
+                            async move {
+                                current.toggle_mount(filesystem).await;
+                            }
+                        ));
+                    }
+                ));
+                filesystem_row.add_suffix(&button);
+
+                row.add_row(&filesystem_row);
+            }
+
+            let eject = gtk::Button::builder()
+                .icon_name("media-eject-symbolic")
+                .tooltip_text("Eject")
+                .valign(gtk::Align::Center)
+                .build();
+            eject.add_css_class("flat");
+            eject.connect_clicked(glib::clone!(
+                #[weak(rename_to = current)]
+                self,
+                move |_button| {
+                    let drive = drive.clone();
+                    glib::spawn_future_local(glib::clone!(
+                        #[weak]
+                        current,
+                        async move {
+                            current.eject_drive(drive).await;
+                        }
+                    ));
+                }
+            ));
+            row.add_suffix(&eject);
+
+            list.append(&row);
+        }
+    }
+
+    /// Mounts `filesystem`, or unmounts it if it's mounted
+    async fn toggle_mount(&self, filesystem: storage::Filesystem) {
+        let Some(storage) = self.imp().storage.borrow().clone() else {
+            return;
+        };
+
+        let (verb, result) = if filesystem.is_mounted() {
+            self.set_storage_status(format!("Unmounting {}…", filesystem.name));
+            let result = storage.unmount(&filesystem).await;
+            ("unmount", result.map(|()| String::new()))
+        } else {
+            self.set_storage_status(format!("Mounting {}…", filesystem.name));
+            let result = storage.mount(&filesystem).await;
+            (
+                "mount",
+                result.map(|point| format!("Mounted {} at {}", filesystem.name, point.display())),
+            )
+        };
+
+        match result {
+            Ok(status) => self.set_storage_status(status),
+            Err(err) => {
+                self.set_storage_status(format!("Could not {verb} {}: {err}", filesystem.name))
+            }
+        }
+
+        self.refresh_drives().await;
+    }
+
+    /// Unmounts everything on `drive` and powers it off, so it can be unplugged
+    async fn eject_drive(&self, drive: storage::Drive) {
+        let Some(storage) = self.imp().storage.borrow().clone() else {
+            return;
+        };
+
+        self.set_storage_status(format!("Ejecting {}…", drive.name));
+
+        match storage.eject(&drive).await {
+            Ok(()) => self.set_storage_status(format!("{} can be unplugged", drive.name)),
+            Err(err) => self.set_storage_status(format!("Could not eject {}: {err}", drive.name)),
+        }
+
+        self.refresh_drives().await;
+    }
+
+    /// Shows the battery read by [`watch_battery`], hiding it if there isn't one
+    fn show_battery(&self, battery: Option<&battery::Battery>, level: battery::Level) {
+        let widget = &self.imp().battery;
+        widget.set_visible(battery.is_some());
+
+        let Some(battery) = battery else {
+            return;
+        };
+
+        self.set_battery_icon(battery.icon());
+        self.set_battery_percentage(format!("{:.0}%", battery.percentage));
+        self.set_battery_description(battery.description());
+
+        for (class, active) in [
+            ("warning", level == battery::Level::Warning),
+            ("critical", level == battery::Level::Critical),
+        ] {
+            if active {
+                widget.add_css_class(class);
+            } else {
+                widget.remove_css_class(class);
+            }
+        }
+    }
+
+    /// The NetworkManager client for the Wi-Fi popover, connecting the first time it's needed
+    async fn wifi(&self) -> Result<Wifi, wifi::Error> {
+        if let Some(wifi) = self.imp().wifi.borrow().clone() {
+            return Ok(wifi);
+        }
+
+        let wifi = Wifi::system().await?;
+        self.imp().wifi.replace(Some(wifi.clone()));
+        Ok(wifi)
+    }
+
+    /// Shows the Wi-Fi button if NetworkManager has a Wi-Fi device to use
+    async fn setup_wifi(&self) {
+        let network_manager = self
+            .application()
+            .and_downcast::<App>()
+            .is_some_and(|app| app.unavailable(Service::NetworkManager).is_none());
+        if !network_manager {
+            return;
+        }
+
+        let device = match self.wifi().await {
+            Ok(wifi) => wifi.device().await,
+            Err(err) => Err(err),
+        };
+
+        match device {
+            Ok(_device) => self.imp().wifi_button.set_visible(true),
+            Err(err) => debug!(%err, "Not showing the Wi-Fi picker"),
+        }
+    }
+
+    /// Lists networks again, after asking for a fresh scan if `scan` is set
+    async fn refresh_wifi(&self, scan: bool) {
+        let wifi = match self.wifi().await {
+            Ok(wifi) => wifi,
+            Err(err) => {
+                self.set_wifi_status(err.to_string());
+                return;
+            }
+        };
+
+        let enabled = match wifi.enabled().await {
+            Ok(enabled) => enabled,
+            Err(err) => {
+                self.set_wifi_status(err.to_string());
+                return;
+            }
+        };
+
+        let imp = self.imp();
+        imp.wifi_syncing.set(true);
+        imp.wifi_switch.set_active(enabled);
+        imp.wifi_syncing.set(false);
+
+        if !enabled {
+            self.show_wifi_networks(Vec::new());
+            return;
+        }
+
+        self.update_wifi_networks(&wifi).await;
+
+        if scan {
+            if let Err(err) = wifi.scan().await {
+                // NetworkManager refuses to scan again too soon after the last one
+                debug!(%err, "Could not scan for Wi-Fi networks");
+            }
+
+            glib::timeout_future(Duration::from_secs(3)).await;
+            self.update_wifi_networks(&wifi).await;
+        }
+    }
+
+    async fn update_wifi_networks(&self, wifi: &Wifi) {
+        match wifi.access_points().await {
+            Ok(networks) => self.show_wifi_networks(networks),
+            Err(err) => self.set_wifi_status(err.to_string()),
+        }
+    }
+
+    fn show_wifi_networks(&self, networks: Vec<wifi::AccessPoint>) {
+        let list = &self.imp().wifi_networks;
+        list.remove_all();
+
+        for network in &networks {
+            let state = if network.active {
+                Some("Connected")
+            } else if network.connection.is_some() {
+                Some("Saved")
+            } else if !network.supported() {
+                Some("Set up in the network settings")
+            } else {
+                None
+            };
+
+            let row = adw::ActionRow::builder()
+                .title(network.ssid.as_str())
+                .use_markup(false)
+                .subtitle(
+                    [Some(network.security.name()), state]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" · "),
+                )
+                .activatable(true)
+                .build();
+
+            row.add_prefix(&gtk::Image::from_icon_name(network.icon()));
+            if network.security != wifi::Security::Open {
+                row.add_suffix(&gtk::Image::from_icon_name(
+                    "network-wireless-encrypted-symbolic",
+                ));
+            }
+
+            list.append(&row);
+        }
+
+        self.imp().access_points.replace(networks);
+    }
+
+    async fn set_wifi_enabled(&self, enabled: bool) {
+        let result = match self.wifi().await {
+            Ok(wifi) => wifi.set_enabled(enabled).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(()) => {
+                self.set_wifi_status(String::new());
+                // give the radio a moment to come up before looking for networks
+                glib::timeout_future(Duration::from_secs(1)).await;
+            }
+            Err(err) => self.set_wifi_status(format!(
+                "Could not turn Wi-Fi {}: {err}",
+                if enabled { "on" } else { "off" }
+            )),
+        }
+
+        self.refresh_wifi(enabled).await;
+    }
+
+    async fn connect_wifi(&self, network: wifi::AccessPoint, passphrase: Option<String>) {
+        self.set_wifi_status(format!("Connecting to {}…", network.ssid));
+
+        let result = match self.wifi().await {
+            Ok(wifi) => wifi.connect(&network, passphrase.as_deref()).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(()) => {
+                self.set_wifi_status(String::new());
+                glib::timeout_future(Duration::from_secs(2)).await;
+                self.refresh_wifi(false).await;
+            }
+            Err(err) => {
+                self.set_wifi_status(format!("Could not connect to {}: {err}", network.ssid))
+            }
+        }
+    }
+
+    /// Draws the usage history into the sparklines in the bar and the charts in its popover
+    fn setup_usage_graphs(&self) {
+        use history::{Line, Series, CAPACITY, RECENT};
+
+        let imp = self.imp();
+        // how many samples fit across each graph, and what's drawn on it
+        let graphs: [(gtk::DrawingArea, usize, Series); 4] = [
+            (imp.cpu_sparkline.get(), RECENT, |history, count| {
+                vec![(history.series(count, |s| s.cpu), Line::Filled)]
+            }),
+            (imp.ram_sparkline.get(), RECENT, |history, count| {
+                vec![
+                    (history.series(count, |s| s.ram), Line::Filled),
+                    (history.series(count, |s| s.swap), Line::Dashed),
+                ]
+            }),
+            (imp.cpu_chart.get(), CAPACITY, |history, count| {
+                (0..history.cores())
+                    .map(|core| {
+                        let series = history
+                            .series(count, |s| s.cores.get(core).copied().unwrap_or_default());
+                        (series, Line::Faint)
+                    })
+                    .chain([(history.series(count, |s| s.cpu), Line::Filled)])
+                    .collect()
+            }),
+            (imp.memory_chart.get(), CAPACITY, |history, count| {
+                vec![
+                    (history.series(count, |s| s.ram), Line::Filled),
+                    (history.series(count, |s| s.swap), Line::Dashed),
+                ]
+            }),
+        ];
+
+        for (graph, slots, series) in graphs {
+            graph.set_draw_func(glib::clone!(
+                #[weak(rename_to = current)]
+                self,
+                move |area, cr, width, height| {
+                    let color = area.color();
+                    let history = current.imp().history.borrow();
+
+                    for (values, line) in series(&history, slots) {
+                        if let Err(err) = history::draw(
+                            cr,
+                            width as f64,
+                            height as f64,
+                            &color,
+                            &values,
+                            slots,
+                            line,
+                        ) {
+                            debug!(%err, "Could not draw usage graph");
+                            return;
+                        }
+                    }
+                }
+            ));
+        }
+    }
+
     async fn update_weather(&self) {
         let weather =
             TOKIO_RUNTIME.block_on(self.imp().weather_service.get_weather(&self.location()));
@@ -176,8 +1077,12 @@ impl Top {
                     _ => "black",
                 };
 
+                let condition = weather.current_condition().desc().trim_end().trim();
+                // a change in the weather is worth showing even when the bar is hidden
+                let changed = !self.weather_desc().is_empty() && self.weather_desc() != condition;
+
                 self.set_weather_temp(temp.value as f32);
-                self.set_weather_desc(weather.current_condition().desc().trim_end().trim());
+                self.set_weather_desc(condition);
                 self.set_weather_temp_min(temp.min as f32);
                 self.set_weather_temp_max(temp.max as f32);
                 self.set_weather_temp_desc(desc);
@@ -197,15 +1102,29 @@ impl Top {
                     "Humidity: {}%",
                     weather.current_condition().humidity()
                 ));
+                self.set_humidity_value(
+                    weather
+                        .current_condition()
+                        .humidity()
+                        .parse::<u32>()
+                        .unwrap_or_default(),
+                );
+                self.set_cloud_cover_value(weather.current_condition().cloud_cover() as u32);
+                self.set_uv_value(uv_index as u32);
+                self.set_weather_updated(chrono::Local::now().timestamp());
 
                 self.set_weather_icon(match icon {
                     Ok(icon) => icon,
                     Err(e) => {
-                        eprintln!("Failed to get weather icon for code {e}");
+                        warn!(code = %e, "No weather icon for code");
                         "question-round-outlined-symbolic"
                     }
                 });
 
+                if changed {
+                    self.peek();
+                }
+
                 self.daily_weather_entries().remove_all();
 
                 for day in weather.weather() {
@@ -217,7 +1136,7 @@ impl Top {
                     let icon = match icon {
                         Ok(icon) => icon,
                         Err(e) => {
-                            eprintln!("Failed to get weather icon for code {e}");
+                            warn!(code = %e, "No weather icon for code");
                             "question-round-outlined-symbolic"
                         }
                     };
@@ -255,7 +1174,7 @@ impl Top {
                     self.daily_weather_entries().append(&container);
                 }
             }
-            Err(e) => eprintln!("Failed to get weather: {:?}", e),
+            Err(e) => error!(err = ?e, "Failed to get weather"),
         }
     }
 
@@ -333,8 +1252,12 @@ impl Top {
                     current.wallpaper_entries().append(&item);
                 }
             }
+
+            current.select_wallpaper();
         });
 
+        self.connect_wallpaper_notify(|current| current.select_wallpaper());
+
         // reference counting shenanigans
         let obj = self.clone();
         self.imp()
@@ -343,7 +1266,7 @@ impl Top {
                 let entry = item.downcast_ref::<WallpaperEntryObject>().unwrap();
                 let image_path = entry.path();
 
-                println!("Creating wallpaper entry: {:?}", image_path);
+                debug!(path = ?image_path, "Creating wallpaper entry");
                 let image = gtk::Picture::for_filename(&image_path);
                 image.add_css_class("wallpaper-image");
                 // due to ultrawide base res is 2560x1080
@@ -370,8 +1293,11 @@ impl Top {
 
                         let args = args.split(' ');
                         if let Err(command) = Command::new(command).args(args).spawn() {
-                            eprintln!("Failed to execute command: {:?}", command);
+                            error!(err = %command, "Failed to run wallpaper command");
+                            return;
                         };
+
+                        obj.set_wallpaper(image_path.to_string_lossy().to_string());
                     }
                 ));
 
@@ -381,6 +1307,19 @@ impl Top {
         self.notify_wallpaper_folder();
     }
 
+    /// Highlights the last wallpaper picked, if it's in the list
+    fn select_wallpaper(&self) {
+        let wallpaper = PathBuf::from(self.wallpaper());
+        let row = self
+            .wallpaper_entries()
+            .iter::<WallpaperEntryObject>()
+            .filter_map(Result::ok)
+            .position(|entry| entry.path() == wallpaper)
+            .and_then(|index| self.imp().wallpaper_items.row_at_index(index as i32));
+
+        self.imp().wallpaper_items.select_row(row.as_ref());
+    }
+
     fn temperature_unit(&self) -> weather::TemperatureUnit {
         if self.use_metric_units() {
             weather::TemperatureUnit::Celsius
@@ -390,6 +1329,174 @@ impl Top {
     }
 }
 
+/// Samples the system for every top bar, and checks the alert rules against it. It's sampled once
+/// for the whole app, so every monitor shows the same readings and an alert is sent once.
+pub fn watch_system(app: &App) {
+    let app = app.downgrade();
+    glib::spawn_future_local(async move {
+        let mut sys = System::new_all();
+        let mut network = network::Sampler::default();
+        let mut sensors = sensors::Sensors::default();
+        let mut storage = storage::Sampler::default();
+        let users = Users::new_with_refreshed_list();
+        let mut alerts = alerts::Alerts::default();
+        let mut stream = glib::interval_stream(history::INTERVAL);
+        sys.refresh_all();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        sys.refresh_cpu_usage();
+
+        while let Some(app) = app.upgrade() {
+            let interfaces = network.sample();
+            let connection =
+                network::primary_connection(app.unavailable(Service::NetworkManager).is_none())
+                    .await;
+            let config = app.config();
+            let sample = sample_usage(&sys);
+            let (by_cpu, by_memory) = processes::busiest(&sys, &users, config.processes.count);
+            let temperatures = sensors.read();
+            storage.refresh();
+            let checks = alerts.check(&config.alerts, |rule| match rule.resource {
+                Resource::Cpu => Some(sample.cpu * 100.0),
+                Resource::Ram => Some(sample.ram * 100.0),
+                Resource::Swap => Some(sample.swap * 100.0),
+                Resource::Disk => storage
+                    .usage(rule.mount.as_deref().unwrap_or(Path::new("/")))
+                    .map(|usage| usage.fraction() as f32 * 100.0),
+                Resource::Temperature => sensors::chosen(
+                    &temperatures,
+                    rule.sensor.as_deref().or(config.sensors.sensor.as_deref()),
+                )
+                .and_then(|reading| reading.temperature),
+            });
+
+            for top in app.outputs().iter().filter_map(|output| output.top().ok()) {
+                top.update_system_stats(&sys, &sample);
+                top.update_processes(&by_cpu, &by_memory);
+                top.update_network(&interfaces, connection.as_ref());
+                top.update_sensors(&temperatures);
+                top.update_storage(&storage);
+                top.show_alerts(&checks);
+            }
+
+            for check in checks.iter().filter(|check| check.started) {
+                let description = alerts::describe(&check.rule);
+
+                notify::Notification {
+                    summary: format!("{} is running high", check.rule.resource.name()),
+                    body: match check.value {
+                        Some(value) => format!(
+                            "{description}, now at {}",
+                            alerts::format_value(check.rule.resource, value)
+                        ),
+                        None => description,
+                    },
+                    icon: "dialog-warning-symbolic".to_string(),
+                    urgency: notify::Urgency::Critical,
+                }
+                .send_later();
+            }
+            drop(config);
+            drop(app);
+
+            if stream.next().await.is_none() {
+                break;
+            }
+            sys.refresh_all();
+        }
+    });
+}
+
+/// CPU and memory usage, each from 0 to 1
+fn sample_usage(sys: &System) -> history::Sample {
+    let ratio = |used: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            (used as f64 / total as f64) as f32
+        }
+    };
+
+    history::Sample {
+        cpu: sys.global_cpu_usage() / 100.0,
+        cores: sys
+            .cpus()
+            .iter()
+            .map(|cpu| cpu.cpu_usage() / 100.0)
+            .collect(),
+        ram: ratio(sys.used_memory(), sys.total_memory()),
+        swap: ratio(sys.used_swap(), sys.total_swap()),
+    }
+}
+
+/// Reads the battery for every top bar, and notifies when it runs low. It's read once for the
+/// whole app, so a warning is sent once however many monitors there are.
+pub fn watch_battery(app: &App) {
+    let app = app.downgrade();
+    glib::spawn_future_local(async move {
+        let mut warnings = battery::Warnings::default();
+        let mut stream = glib::interval_stream(history::INTERVAL);
+
+        while let Some(app) = app.upgrade() {
+            let battery = battery::Battery::read(app.unavailable(Service::UPower).is_none()).await;
+            let config = app.config().battery.clone();
+            let level = battery
+                .as_ref()
+                .map(|battery| battery::Level::of(battery, config.warning, config.critical))
+                .unwrap_or_default();
+
+            for top in app.outputs().iter().filter_map(|output| output.top().ok()) {
+                top.show_battery(battery.as_ref(), level);
+            }
+
+            let warned = battery
+                .as_ref()
+                .and_then(|battery| warnings.update(battery, config.warning, config.critical))
+                .filter(|_| config.notify);
+
+            if let (Some(battery), Some(level)) = (&battery, warned) {
+                let (summary, urgency) = match level {
+                    battery::Level::Critical => {
+                        ("Battery critically low", notify::Urgency::Critical)
+                    }
+                    _ => ("Battery low", notify::Urgency::Normal),
+                };
+
+                notify::Notification {
+                    summary: summary.to_string(),
+                    body: format!("{:.0}% · {}", battery.percentage, battery.description()),
+                    icon: battery.icon(),
+                    urgency,
+                }
+                .send_later();
+            }
+            drop(app);
+
+            if stream.next().await.is_none() {
+                break;
+            }
+        }
+    });
+}
+
+/// Whether `button`'s popover is showing
+fn popover_open(button: &gtk::MenuButton) -> bool {
+    button.popover().is_some_and(|popover| popover.is_visible())
+}
+
+/// The difference between two time zones, e.g. `+5 h`, `-3 h 30 min` or `Same time`
+fn format_offset(minutes: i32) -> String {
+    if minutes == 0 {
+        return "Same time".to_string();
+    }
+
+    let sign = if minutes > 0 { '+' } else { '-' };
+    match (minutes.abs() / 60, minutes.abs() % 60) {
+        (0, minutes) => format!("{sign}{minutes} min"),
+        (hours, 0) => format!("{sign}{hours} h"),
+        (hours, minutes) => format!("{sign}{hours} h {minutes} min"),
+    }
+}
+
 mod wallpaper {
     use super::*;
 
@@ -432,3 +1539,33 @@ mod wallpaper {
         impl ObjectImpl for WallpaperEntryObject {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_whole_hours_ahead() {
+        assert_eq!(format_offset(5 * 60), "+5 h");
+    }
+
+    #[test]
+    fn formats_hours_behind() {
+        assert_eq!(format_offset(-8 * 60), "-8 h");
+        assert_eq!(format_offset(-(3 * 60 + 30)), "-3 h 30 min");
+    }
+
+    #[test]
+    fn formats_half_hour_zones() {
+        // e.g. India, at +05:30, from UTC
+        assert_eq!(format_offset(5 * 60 + 30), "+5 h 30 min");
+        assert_eq!(format_offset(30), "+30 min");
+        assert_eq!(format_offset(-30), "-30 min");
+    }
+
+    #[test]
+    fn formats_the_same_zone() {
+        // e.g. UTC, from London in winter
+        assert_eq!(format_offset(0), "Same time");
+    }
+}
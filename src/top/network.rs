@@ -0,0 +1,242 @@
+//! Network status for the top bar.
+//!
+//! Throughput comes from the kernel's per-interface byte counters, so it works without
+//! NetworkManager. When NetworkManager is running it's asked for the name and type of the primary
+//! connection, otherwise the interface with the default route is used.
+
+use std::{fs, path::Path, time::Instant};
+
+use gtk::{gio, glib, prelude::*};
+use sysinfo::Networks;
+use tracing::debug;
+
+pub(super) const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+pub(super) const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Wired,
+    Wifi,
+    Vpn,
+    Other,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Wired => "Wired",
+            Kind::Wifi => "Wi-Fi",
+            Kind::Vpn => "VPN",
+            Kind::Other => "Other",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Kind::Wired => "network-wired-symbolic",
+            Kind::Wifi => "network-wireless-symbolic",
+            Kind::Vpn => "network-vpn-symbolic",
+            Kind::Other => "network-transmit-receive-symbolic",
+        }
+    }
+
+    /// Guesses the kind of an interface from what the kernel exposes about it
+    fn of_interface(name: &str) -> Self {
+        let sysfs = Path::new("/sys/class/net").join(name);
+
+        if sysfs.join("wireless").exists() || sysfs.join("phy80211").exists() {
+            Kind::Wifi
+        } else if sysfs.join("tun_flags").exists() || name.starts_with("wg") {
+            Kind::Vpn
+        } else if sysfs.join("device").exists() {
+            Kind::Wired
+        } else {
+            Kind::Other
+        }
+    }
+
+    /// The kind of a NetworkManager connection type, e.g. `802-11-wireless`
+    fn of_connection_type(connection_type: &str) -> Self {
+        match connection_type {
+            "802-3-ethernet" => Kind::Wired,
+            "802-11-wireless" => Kind::Wifi,
+            "vpn" | "wireguard" | "tun" => Kind::Vpn,
+            _ => Kind::Other,
+        }
+    }
+}
+
+/// A network interface and its throughput since the last sample
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub name: String,
+    pub kind: Kind,
+    pub addresses: Vec<String>,
+    /// Bytes per second
+    pub down: f64,
+    pub up: f64,
+}
+
+/// The connection most traffic goes through
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// The connection's name in NetworkManager, or the interface name without it
+    pub name: String,
+    pub kind: Kind,
+    pub interface: Option<String>,
+}
+
+/// Samples the kernel's byte counters, turning them into rates
+#[derive(Debug)]
+pub struct Sampler {
+    networks: Networks,
+    last: Instant,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            networks: Networks::new_with_refreshed_list(),
+            last: Instant::now(),
+        }
+    }
+}
+
+impl Sampler {
+    /// Every interface except loopback, sorted by name
+    pub fn sample(&mut self) -> Vec<Interface> {
+        self.networks.refresh(true);
+        let elapsed = self.last.elapsed().as_secs_f64().max(f64::EPSILON);
+        self.last = Instant::now();
+
+        let mut interfaces: Vec<_> = self
+            .networks
+            .iter()
+            .filter(|(name, _data)| name.as_str() != "lo")
+            .map(|(name, data)| Interface {
+                name: name.clone(),
+                kind: Kind::of_interface(name),
+                addresses: data
+                    .ip_networks()
+                    .iter()
+                    .map(|network| format!("{}/{}", network.addr, network.prefix))
+                    .collect(),
+                down: data.received() as f64 / elapsed,
+                up: data.transmitted() as f64 / elapsed,
+            })
+            .collect();
+
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        interfaces
+    }
+}
+
+/// The primary connection according to NetworkManager, or from the routing table if it isn't
+/// running. None when offline.
+pub async fn primary_connection(network_manager: bool) -> Option<Connection> {
+    if network_manager {
+        match nm_primary_connection().await {
+            Ok(connection) => return connection,
+            Err(err) => debug!(%err, "Could not ask NetworkManager for the primary connection"),
+        }
+    }
+
+    default_route().map(|interface| Connection {
+        name: interface.clone(),
+        kind: Kind::of_interface(&interface),
+        interface: Some(interface),
+    })
+}
+
+async fn nm_primary_connection() -> Result<Option<Connection>, glib::Error> {
+    let bus = gio::bus_get_future(gio::BusType::System).await?;
+
+    let path = nm_property(&bus, NM_PATH, NM_BUS_NAME, "PrimaryConnection").await?;
+    let Some(path) = path.str().filter(|path| *path != "/").map(str::to_string) else {
+        return Ok(None);
+    };
+
+    let interface = "org.freedesktop.NetworkManager.Connection.Active";
+    let name = nm_property(&bus, &path, interface, "Id").await?;
+    let connection_type = nm_property(&bus, &path, interface, "Type").await?;
+    let devices: Vec<glib::variant::ObjectPath> = nm_property(&bus, &path, interface, "Devices")
+        .await?
+        .get()
+        .unwrap_or_default();
+
+    let device = match devices.first() {
+        Some(device) => nm_property(
+            &bus,
+            device.as_str(),
+            "org.freedesktop.NetworkManager.Device",
+            "Interface",
+        )
+        .await?
+        .str()
+        .map(str::to_string),
+        None => None,
+    };
+
+    Ok(Some(Connection {
+        name: name.str().unwrap_or_default().to_string(),
+        kind: Kind::of_connection_type(connection_type.str().unwrap_or_default()),
+        interface: device,
+    }))
+}
+
+/// Reads a property of a NetworkManager object
+pub(super) async fn nm_property(
+    bus: &gio::DBusConnection,
+    path: &str,
+    interface: &str,
+    property: &str,
+) -> Result<glib::Variant, glib::Error> {
+    let reply = bus
+        .call_future(
+            Some(NM_BUS_NAME),
+            path,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            Some(&(interface, property).to_variant()),
+            Some(glib::VariantTy::new("(v)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            1000,
+        )
+        .await?;
+
+    Ok(reply.child_value(0).as_variant().unwrap_or(reply))
+}
+
+/// The interface with the lowest-metric IPv4 default route
+fn default_route() -> Option<String> {
+    let routes = fs::read_to_string("/proc/net/route").ok()?;
+
+    // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let metric = fields.get(6)?.parse::<u32>().ok()?;
+            (fields.get(1) == Some(&"00000000")).then(|| (metric, fields[0].to_string()))
+        })
+        .min()
+        .map(|(_metric, interface)| interface)
+}
+
+/// Formats a rate in bytes per second, e.g. `1.2 MB/s`
+pub fn format_rate(mut rate: f64) -> String {
+    const UNITS: [&str; 4] = ["B/s", "KB/s", "MB/s", "GB/s"];
+
+    let mut unit = 0;
+    while rate >= 1000.0 && unit < UNITS.len() - 1 {
+        rate /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{rate:.0} {}", UNITS[unit])
+    } else {
+        format!("{rate:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,125 @@
+//! The busiest processes, for the usage popover, and ending them.
+
+use std::fmt;
+
+use sysinfo::{Pid, ProcessesToUpdate, System, Users};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Asks the process to exit, letting it clean up
+    Terminate,
+    /// Ends it straight away
+    Kill,
+}
+
+impl Signal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Signal::Terminate => "terminate",
+            Signal::Kill => "kill",
+        }
+    }
+
+    fn to_sysinfo(self) -> sysinfo::Signal {
+        match self {
+            Signal::Terminate => sysinfo::Signal::Term,
+            Signal::Kill => sysinfo::Signal::Kill,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub pid: Pid,
+    pub name: String,
+    /// The owner's name, or their ID if they don't have one
+    pub user: Option<String>,
+    /// Whether the user running the shell owns it
+    pub own: bool,
+    /// Percent of one core, so it can go over 100
+    pub cpu: f32,
+    /// Bytes of RAM
+    pub memory: u64,
+    /// Seconds since the epoch, to tell it apart from a later process with the same PID
+    start_time: u64,
+}
+
+impl Process {
+    /// Sends `signal`, if the process is still the same one that was listed
+    pub fn send(&self, signal: Signal) -> Result<(), Error> {
+        let mut sys = System::new();
+        sys.refresh_processes(ProcessesToUpdate::Some(&[self.pid]), true);
+
+        let process = sys
+            .process(self.pid)
+            .filter(|process| process.start_time() == self.start_time)
+            .ok_or(Error::Exited)?;
+
+        match process.kill_with(signal.to_sysinfo()) {
+            Some(true) => Ok(()),
+            Some(false) => Err(Error::NotPermitted),
+            None => Err(Error::Unsupported),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Exited,
+    /// Usually because it's someone else's
+    NotPermitted,
+    /// The signal doesn't exist on this platform
+    Unsupported,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Exited => write!(f, "it has already exited"),
+            Error::NotPermitted => write!(f, "permission denied"),
+            Error::Unsupported => write!(f, "the signal isn't supported here"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The `count` processes using the most CPU, and the ones using the most memory, busiest first
+pub fn busiest(sys: &System, users: &Users, count: usize) -> (Vec<Process>, Vec<Process>) {
+    let me = sysinfo::get_current_pid()
+        .ok()
+        .and_then(|pid| sys.process(pid))
+        .and_then(|process| process.user_id());
+
+    let mut processes: Vec<Process> = sys
+        .processes()
+        .values()
+        // threads show up as processes on Linux, and would repeat their process' usage
+        .filter(|process| process.thread_kind().is_none())
+        .map(|process| {
+            let owner = process.user_id();
+
+            Process {
+                pid: process.pid(),
+                name: process.name().to_string_lossy().to_string(),
+                user: owner.map(|uid| {
+                    users
+                        .get_user_by_id(uid)
+                        .map_or_else(|| (**uid).to_string(), |user| user.name().to_string())
+                }),
+                own: owner.is_some() && owner == me,
+                cpu: process.cpu_usage(),
+                memory: process.memory(),
+                start_time: process.start_time(),
+            }
+        })
+        .collect();
+
+    processes.sort_by(|a, b| b.cpu.total_cmp(&a.cpu));
+    let by_cpu = processes.iter().take(count).cloned().collect();
+
+    processes.sort_by(|a, b| b.memory.cmp(&a.memory));
+    processes.truncate(count);
+
+    (by_cpu, processes)
+}
@@ -0,0 +1,93 @@
+//! Hardware temperatures for the top bar, from the kernel's hwmon sensors.
+
+use sysinfo::Components;
+
+use super::weather::TemperatureUnit;
+
+/// Labels of the sensors most CPUs report their overall temperature on, most preferred first
+const CPU_PACKAGE: [&str; 4] = ["Package id 0", "Tctl", "Tdie", "cpu_thermal"];
+
+#[derive(Debug, Clone)]
+pub struct Reading {
+    /// e.g. `coretemp Package id 0`
+    pub label: String,
+    /// In °C, like the rest
+    pub temperature: Option<f32>,
+    /// The highest temperature seen since the sensor was first read
+    pub max: Option<f32>,
+    /// Where the hardware starts throttling or shuts down
+    pub critical: Option<f32>,
+}
+
+impl Reading {
+    /// Whether it's past `warning` °C, or its own critical temperature if that's lower
+    pub fn is_hot(&self, warning: f32) -> bool {
+        let threshold = self
+            .critical
+            .map_or(warning, |critical| critical.min(warning));
+        self.temperature
+            .is_some_and(|temperature| temperature >= threshold)
+    }
+}
+
+/// Reads every temperature sensor
+#[derive(Debug)]
+pub struct Sensors {
+    components: Components,
+}
+
+impl Default for Sensors {
+    fn default() -> Self {
+        Self {
+            components: Components::new_with_refreshed_list(),
+        }
+    }
+}
+
+impl Sensors {
+    /// Every sensor with a reading, sorted by label
+    pub fn read(&mut self) -> Vec<Reading> {
+        self.components.refresh(true);
+
+        let mut readings: Vec<_> = self
+            .components
+            .iter()
+            .filter(|component| component.temperature().is_some())
+            .map(|component| Reading {
+                label: component.label().to_string(),
+                temperature: component.temperature(),
+                max: component.max(),
+                critical: component.critical(),
+            })
+            .collect();
+
+        readings.sort_by(|a, b| a.label.cmp(&b.label));
+        readings
+    }
+}
+
+/// The sensor to show in the bar: the first whose label contains `sensor`, otherwise the CPU
+/// package, otherwise the hottest
+pub fn chosen<'a>(readings: &'a [Reading], sensor: Option<&str>) -> Option<&'a Reading> {
+    let containing = |name: &str| readings.iter().find(|reading| reading.label.contains(name));
+
+    if let Some(reading) = sensor.and_then(containing) {
+        return Some(reading);
+    }
+
+    CPU_PACKAGE.into_iter().find_map(containing).or_else(|| {
+        readings.iter().max_by(|a, b| {
+            a.temperature
+                .unwrap_or_default()
+                .total_cmp(&b.temperature.unwrap_or_default())
+        })
+    })
+}
+
+/// Formats a temperature in °C, e.g. `64°C` or `147°F`
+pub fn format_temperature(celsius: f32, unit: TemperatureUnit) -> String {
+    match unit {
+        TemperatureUnit::Celsius => format!("{celsius:.0}°C"),
+        TemperatureUnit::Fahrenheit => format!("{:.0}°F", celsius * 9.0 / 5.0 + 32.0),
+    }
+}
@@ -0,0 +1,386 @@
+//! Disk usage and removable drives for the top bar.
+//!
+//! Usage comes from the kernel through sysinfo. Removable drives are found, mounted and ejected
+//! through UDisks2, the same way file managers do, so polkit decides what the user may do.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use gtk::{
+    gio,
+    glib::{self, variant::ObjectPath},
+    prelude::*,
+};
+use sysinfo::Disks;
+
+const UDISKS_BUS_NAME: &str = "org.freedesktop.UDisks2";
+const UDISKS_PATH: &str = "/org/freedesktop/UDisks2";
+const DRIVE: &str = "org.freedesktop.UDisks2.Drive";
+const BLOCK: &str = "org.freedesktop.UDisks2.Block";
+const FILESYSTEM: &str = "org.freedesktop.UDisks2.Filesystem";
+
+/// How full a mounted filesystem is
+#[derive(Debug, Clone)]
+pub struct Usage {
+    pub mount_point: PathBuf,
+    /// Bytes
+    pub used: u64,
+    pub total: u64,
+}
+
+impl Usage {
+    /// From 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.used as f64 / self.total as f64
+        }
+    }
+}
+
+/// Reads the usage of mounted filesystems
+#[derive(Debug)]
+pub struct Sampler {
+    disks: Disks,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            disks: Disks::new_with_refreshed_list(),
+        }
+    }
+}
+
+impl Sampler {
+    pub fn refresh(&mut self) {
+        self.disks.refresh(true);
+    }
+
+    /// The usage of the filesystem mounted at `mount`, as of the last refresh
+    pub fn usage(&self, mount: &Path) -> Option<Usage> {
+        let disk = self.disks.iter().find(|disk| disk.mount_point() == mount)?;
+
+        Some(Usage {
+            mount_point: mount.to_path_buf(),
+            used: disk.total_space().saturating_sub(disk.available_space()),
+            total: disk.total_space(),
+        })
+    }
+}
+
+/// A filesystem on a removable drive
+#[derive(Debug, Clone)]
+pub struct Filesystem {
+    /// The block device's object
+    pub path: ObjectPath,
+    /// The filesystem's label, or the device's name without one
+    pub name: String,
+    /// e.g. `/dev/sdb1`
+    pub device: String,
+    /// Bytes
+    pub size: u64,
+    /// Empty when it isn't mounted
+    pub mount_points: Vec<PathBuf>,
+}
+
+impl Filesystem {
+    pub fn is_mounted(&self) -> bool {
+        !self.mount_points.is_empty()
+    }
+}
+
+/// A drive that can be unplugged, with the filesystems on it
+#[derive(Debug, Clone)]
+pub struct Drive {
+    pub path: ObjectPath,
+    /// Vendor and model, e.g. `SanDisk Cruzer Blade`
+    pub name: String,
+    /// Bytes
+    pub size: u64,
+    /// Whether the media can be ejected, like an optical disc
+    pub ejectable: bool,
+    /// Whether it can be powered off, like a USB stick
+    pub can_power_off: bool,
+    pub filesystems: Vec<Filesystem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    bus: gio::DBusConnection,
+}
+
+impl Storage {
+    /// Talks to UDisks2 on `bus`
+    pub fn new(bus: gio::DBusConnection) -> Self {
+        Self { bus }
+    }
+
+    /// Talks to the real UDisks2, on the system bus
+    pub async fn system() -> Result<Self, glib::Error> {
+        Ok(Self::new(gio::bus_get_future(gio::BusType::System).await?))
+    }
+
+    async fn call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: Option<glib::Variant>,
+        reply_type: &str,
+    ) -> Result<glib::Variant, glib::Error> {
+        self.bus
+            .call_future(
+                Some(UDISKS_BUS_NAME),
+                path,
+                interface,
+                method,
+                args.as_ref(),
+                glib::VariantTy::new(reply_type).ok(),
+                // polkit may ask for a password, e.g. for drives that aren't the user's
+                gio::DBusCallFlags::ALLOW_INTERACTIVE_AUTHORIZATION,
+                // unmounting waits for writes to be flushed, which can take a while
+                60_000,
+            )
+            .await
+    }
+
+    /// Calls `f` whenever a drive or filesystem comes, goes or changes, like a drive being plugged
+    /// in or mounted. Other disks' jobs and block devices are left out.
+    pub fn watch(&self, f: impl Fn() + 'static) -> Vec<gio::SignalSubscriptionId> {
+        let f = Rc::new(f);
+        let subscribe = |interface: &str, member: Option<&str>, arg0: Option<&str>| {
+            let f = f.clone();
+            self.bus.signal_subscribe(
+                Some(UDISKS_BUS_NAME),
+                Some(interface),
+                member,
+                None,
+                arg0,
+                gio::DBusSignalFlags::NONE,
+                move |_bus, _sender, _path, _interface, _signal, _args| f(),
+            )
+        };
+
+        vec![
+            // InterfacesAdded and InterfacesRemoved
+            subscribe("org.freedesktop.DBus.ObjectManager", None, None),
+            subscribe(
+                "org.freedesktop.DBus.Properties",
+                Some("PropertiesChanged"),
+                Some(DRIVE),
+            ),
+            subscribe(
+                "org.freedesktop.DBus.Properties",
+                Some("PropertiesChanged"),
+                Some(FILESYSTEM),
+            ),
+        ]
+    }
+
+    /// Stops calling what `watch` was given
+    pub fn unwatch(&self, watches: Vec<gio::SignalSubscriptionId>) {
+        for watch in watches {
+            self.bus.signal_unsubscribe(watch);
+        }
+    }
+
+    /// Removable drives with at least one filesystem, sorted by name
+    pub async fn drives(&self) -> Result<Vec<Drive>, glib::Error> {
+        let reply = self
+            .call(
+                UDISKS_PATH,
+                "org.freedesktop.DBus.ObjectManager",
+                "GetManagedObjects",
+                None,
+                "(a{oa{sa{sv}}})",
+            )
+            .await?;
+
+        let objects: Vec<(ObjectPath, HashMap<String, HashMap<String, glib::Variant>>)> = reply
+            .child_value(0)
+            .iter()
+            .filter_map(|object| {
+                let path = object.child_value(0).get()?;
+                Some((path, object.child_value(1).get()?))
+            })
+            .collect();
+
+        let mut drives: Vec<Drive> = objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let drive = interfaces.get(DRIVE)?;
+                let flag = |name: &str| {
+                    drive
+                        .get(name)
+                        .and_then(|value| value.get::<bool>())
+                        .unwrap_or_default()
+                };
+                let text = |name: &str| {
+                    drive
+                        .get(name)
+                        .and_then(|value| value.str().map(str::to_string))
+                        .filter(|text| !text.is_empty())
+                };
+
+                if !flag("Removable") || !flag("MediaAvailable") {
+                    return None;
+                }
+
+                Some(Drive {
+                    path: path.clone(),
+                    name: [text("Vendor"), text("Model")]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    size: drive
+                        .get("Size")
+                        .and_then(|size| size.get::<u64>())
+                        .unwrap_or_default(),
+                    ejectable: flag("Ejectable"),
+                    can_power_off: flag("CanPowerOff"),
+                    filesystems: Vec::new(),
+                })
+            })
+            .collect();
+
+        for (path, interfaces) in &objects {
+            let (Some(block), Some(filesystem)) =
+                (interfaces.get(BLOCK), interfaces.get(FILESYSTEM))
+            else {
+                continue;
+            };
+
+            let hidden = block
+                .get("HintIgnore")
+                .and_then(|ignore| ignore.get::<bool>())
+                .unwrap_or_default();
+            if hidden {
+                continue;
+            }
+
+            let Some(drive_path) = block
+                .get("Drive")
+                .and_then(|drive| drive.get::<ObjectPath>())
+            else {
+                continue;
+            };
+            let Some(drive) = drives
+                .iter_mut()
+                .find(|drive| drive.path.as_str() == drive_path.as_str())
+            else {
+                continue;
+            };
+
+            let device = block
+                .get("PreferredDevice")
+                .or_else(|| block.get("Device"))
+                .and_then(|device| device.get::<Vec<u8>>())
+                .map(|device| bytestring(&device))
+                .unwrap_or_default();
+            let label = block
+                .get("IdLabel")
+                .and_then(|label| label.str().map(str::to_string))
+                .filter(|label| !label.is_empty());
+
+            drive.filesystems.push(Filesystem {
+                path: path.clone(),
+                name: label.unwrap_or_else(|| device.clone()),
+                size: block
+                    .get("Size")
+                    .and_then(|size| size.get::<u64>())
+                    .unwrap_or_default(),
+                mount_points: filesystem
+                    .get("MountPoints")
+                    .and_then(|points| points.get::<Vec<Vec<u8>>>())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|point| PathBuf::from(bytestring(point)))
+                    .collect(),
+                device,
+            });
+        }
+
+        drives.retain(|drive| !drive.filesystems.is_empty());
+        for drive in &mut drives {
+            drive.filesystems.sort_by(|a, b| a.device.cmp(&b.device));
+            if drive.name.is_empty() {
+                drive.name = drive.filesystems[0].device.clone();
+            }
+        }
+        drives.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(drives)
+    }
+
+    /// Mounts `filesystem` wherever UDisks2 picks, usually under `/run/media/$USER`
+    pub async fn mount(&self, filesystem: &Filesystem) -> Result<PathBuf, glib::Error> {
+        let options: HashMap<String, glib::Variant> = HashMap::new();
+        let mount_point = self
+            .call(
+                filesystem.path.as_str(),
+                FILESYSTEM,
+                "Mount",
+                Some((options,).to_variant()),
+                "(s)",
+            )
+            .await?;
+
+        Ok(PathBuf::from(
+            mount_point.child_value(0).str().unwrap_or_default(),
+        ))
+    }
+
+    pub async fn unmount(&self, filesystem: &Filesystem) -> Result<(), glib::Error> {
+        let options: HashMap<String, glib::Variant> = HashMap::new();
+        self.call(
+            filesystem.path.as_str(),
+            FILESYSTEM,
+            "Unmount",
+            Some((options,).to_variant()),
+            "()",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Unmounts everything on `drive`, then powers it off or ejects its media, so it's safe to
+    /// unplug
+    pub async fn eject(&self, drive: &Drive) -> Result<(), glib::Error> {
+        for filesystem in drive.filesystems.iter().filter(|fs| fs.is_mounted()) {
+            self.unmount(filesystem).await?;
+        }
+
+        let method = if drive.can_power_off {
+            "PowerOff"
+        } else if drive.ejectable {
+            "Eject"
+        } else {
+            return Ok(());
+        };
+
+        let options: HashMap<String, glib::Variant> = HashMap::new();
+        self.call(
+            drive.path.as_str(),
+            DRIVE,
+            method,
+            Some((options,).to_variant()),
+            "()",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// A NUL-terminated byte string, as UDisks2 sends paths
+fn bytestring(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).to_string()
+}
@@ -149,7 +149,7 @@ impl CurrentCondition {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum TemperatureUnit {
     Celsius,
     Fahrenheit,
@@ -0,0 +1,853 @@
+//! Picking a Wi-Fi network, through NetworkManager's D-Bus API.
+//!
+//! [`Wifi`] talks to whichever bus it's given, so it can be pointed at a fake NetworkManager on a
+//! private bus (e.g. one started with `gio::TestDBus`) instead of the system one.
+
+use std::{collections::HashMap, fmt};
+
+use gtk::{
+    gio,
+    glib::{self, variant::ObjectPath},
+    prelude::*,
+};
+
+use super::network::{nm_property, NM_BUS_NAME, NM_PATH};
+
+const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const DEVICE: &str = "org.freedesktop.NetworkManager.Device";
+const WIRELESS: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const ACCESS_POINT: &str = "org.freedesktop.NetworkManager.AccessPoint";
+
+/// `NM_DEVICE_TYPE_WIFI`
+const DEVICE_TYPE_WIFI: u32 = 2;
+/// `NM_802_11_AP_FLAGS_PRIVACY`
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+/// `NM_802_11_AP_SEC_KEY_MGMT_*`
+const KEY_MGMT_PSK: u32 = 0x100;
+const KEY_MGMT_802_1X: u32 = 0x200;
+const KEY_MGMT_SAE: u32 = 0x400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    Open,
+    Wep,
+    /// WPA or WPA2 with a passphrase
+    Personal,
+    Wpa3,
+    Enterprise,
+}
+
+impl Security {
+    fn from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> Self {
+        let key_mgmt = wpa_flags | rsn_flags;
+
+        if key_mgmt & KEY_MGMT_802_1X != 0 {
+            Security::Enterprise
+        } else if key_mgmt & KEY_MGMT_PSK != 0 {
+            Security::Personal
+        } else if key_mgmt & KEY_MGMT_SAE != 0 {
+            Security::Wpa3
+        } else if flags & AP_FLAGS_PRIVACY != 0 {
+            Security::Wep
+        } else {
+            Security::Open
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Security::Open => "Open",
+            Security::Wep => "WEP",
+            Security::Personal => "WPA",
+            Security::Wpa3 => "WPA3",
+            Security::Enterprise => "Enterprise",
+        }
+    }
+
+    /// The `802-11-wireless-security` settings for a new connection using `passphrase`
+    fn settings(&self, passphrase: &str) -> Result<HashMap<String, glib::Variant>, Error> {
+        let (key_mgmt, key) = match self {
+            Security::Open => return Ok(HashMap::new()),
+            Security::Wep => ("none", "wep-key0"),
+            Security::Personal => ("wpa-psk", "psk"),
+            Security::Wpa3 => ("sae", "psk"),
+            Security::Enterprise => return Err(Error::Unsupported(*self)),
+        };
+
+        let mut settings = HashMap::from([
+            ("key-mgmt".to_string(), key_mgmt.to_variant()),
+            (key.to_string(), passphrase.to_variant()),
+        ]);
+
+        if let Security::Wep = self {
+            // NM_WEP_KEY_TYPE_PASSPHRASE
+            settings.insert("wep-key-type".to_string(), 2u32.to_variant());
+        }
+
+        Ok(settings)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessPoint {
+    pub path: ObjectPath,
+    pub ssid: String,
+    /// Signal strength, in percent
+    pub strength: u8,
+    pub security: Security,
+    /// The saved connection for this network, if there is one
+    pub connection: Option<ObjectPath>,
+    pub active: bool,
+}
+
+impl AccessPoint {
+    /// Whether connecting needs a passphrase from the user first
+    pub fn needs_passphrase(&self) -> bool {
+        self.supported() && self.connection.is_none() && self.security != Security::Open
+    }
+
+    /// Whether it can be connected to from here. Enterprise networks need more than a passphrase,
+    /// so only ones already saved in the network settings can be.
+    pub fn supported(&self) -> bool {
+        self.connection.is_some() || self.security != Security::Enterprise
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self.strength {
+            80.. => "network-wireless-signal-excellent-symbolic",
+            55.. => "network-wireless-signal-good-symbolic",
+            30.. => "network-wireless-signal-ok-symbolic",
+            5.. => "network-wireless-signal-weak-symbolic",
+            _ => "network-wireless-signal-none-symbolic",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    DBus(glib::Error),
+    /// There's no Wi-Fi device to use
+    NoDevice,
+    NeedsPassphrase,
+    Unsupported(Security),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DBus(err) => write!(f, "{err}"),
+            Error::NoDevice => write!(f, "no Wi-Fi device found"),
+            Error::NeedsPassphrase => write!(f, "a passphrase is needed"),
+            Error::Unsupported(security) => write!(
+                f,
+                "{} networks have to be set up in the network settings",
+                security.name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<glib::Error> for Error {
+    fn from(err: glib::Error) -> Self {
+        Error::DBus(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Wifi {
+    bus: gio::DBusConnection,
+}
+
+impl Wifi {
+    /// Talks to NetworkManager on `bus`
+    pub fn new(bus: gio::DBusConnection) -> Self {
+        Self { bus }
+    }
+
+    /// Talks to the real NetworkManager, on the system bus
+    pub async fn system() -> Result<Self, Error> {
+        Ok(Self::new(gio::bus_get_future(gio::BusType::System).await?))
+    }
+
+    async fn call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: Option<glib::Variant>,
+        reply_type: &str,
+    ) -> Result<glib::Variant, glib::Error> {
+        self.bus
+            .call_future(
+                Some(NM_BUS_NAME),
+                path,
+                interface,
+                method,
+                args.as_ref(),
+                glib::VariantTy::new(reply_type).ok(),
+                gio::DBusCallFlags::NONE,
+                5000,
+            )
+            .await
+    }
+
+    /// Whether the Wi-Fi radio is on
+    pub async fn enabled(&self) -> Result<bool, Error> {
+        Ok(
+            nm_property(&self.bus, NM_PATH, NM_BUS_NAME, "WirelessEnabled")
+                .await?
+                .get()
+                .unwrap_or_default(),
+        )
+    }
+
+    pub async fn set_enabled(&self, enabled: bool) -> Result<(), Error> {
+        self.call(
+            NM_PATH,
+            "org.freedesktop.DBus.Properties",
+            "Set",
+            Some((NM_BUS_NAME, "WirelessEnabled", enabled.to_variant()).to_variant()),
+            "()",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The first Wi-Fi device
+    pub async fn device(&self) -> Result<ObjectPath, Error> {
+        let devices: Vec<ObjectPath> = self
+            .call(NM_PATH, NM_BUS_NAME, "GetDevices", None, "(ao)")
+            .await?
+            .child_value(0)
+            .get()
+            .unwrap_or_default();
+
+        for device in devices {
+            let device_type = nm_property(&self.bus, device.as_str(), DEVICE, "DeviceType")
+                .await?
+                .get::<u32>();
+
+            if device_type == Some(DEVICE_TYPE_WIFI) {
+                return Ok(device);
+            }
+        }
+
+        Err(Error::NoDevice)
+    }
+
+    /// Asks the device to look for networks. They show up in [`Self::access_points`] a few
+    /// seconds later.
+    pub async fn scan(&self) -> Result<(), Error> {
+        let device = self.device().await?;
+        let options: HashMap<String, glib::Variant> = HashMap::new();
+
+        self.call(
+            device.as_str(),
+            WIRELESS,
+            "RequestScan",
+            Some((options,).to_variant()),
+            "()",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Visible networks, one per name, with the connected one first and the rest strongest first
+    pub async fn access_points(&self) -> Result<Vec<AccessPoint>, Error> {
+        let device = self.device().await?;
+        let active = nm_property(&self.bus, device.as_str(), WIRELESS, "ActiveAccessPoint")
+            .await?
+            .get::<ObjectPath>();
+        let paths: Vec<ObjectPath> = self
+            .call(
+                device.as_str(),
+                WIRELESS,
+                "GetAllAccessPoints",
+                None,
+                "(ao)",
+            )
+            .await?
+            .child_value(0)
+            .get()
+            .unwrap_or_default();
+        let known = self.known_networks().await?;
+
+        let mut networks: Vec<AccessPoint> = Vec::new();
+        for path in paths {
+            let properties: HashMap<String, glib::Variant> = self
+                .call(
+                    path.as_str(),
+                    "org.freedesktop.DBus.Properties",
+                    "GetAll",
+                    Some((ACCESS_POINT,).to_variant()),
+                    "(a{sv})",
+                )
+                .await?
+                .child_value(0)
+                .get()
+                .unwrap_or_default();
+            let flags = |name: &str| {
+                properties
+                    .get(name)
+                    .and_then(|flags| flags.get::<u32>())
+                    .unwrap_or_default()
+            };
+
+            let ssid = properties
+                .get("Ssid")
+                .and_then(|ssid| ssid.get::<Vec<u8>>())
+                .unwrap_or_default();
+            // hidden networks don't have a name to show
+            if ssid.is_empty() {
+                continue;
+            }
+
+            let ssid = String::from_utf8_lossy(&ssid).to_string();
+            let strength = properties
+                .get("Strength")
+                .and_then(|strength| strength.get::<u8>())
+                .unwrap_or_default();
+            let is_active = active
+                .as_ref()
+                .is_some_and(|active| active.as_str() == path.as_str());
+
+            // several access points can share a name, keep the strongest
+            if let Some(network) = networks.iter_mut().find(|network| network.ssid == ssid) {
+                network.active |= is_active;
+                if strength > network.strength {
+                    network.path = path;
+                    network.strength = strength;
+                }
+                continue;
+            }
+
+            networks.push(AccessPoint {
+                path,
+                connection: known.get(&ssid).cloned(),
+                ssid,
+                strength,
+                security: Security::from_flags(
+                    flags("Flags"),
+                    flags("WpaFlags"),
+                    flags("RsnFlags"),
+                ),
+                active: is_active,
+            });
+        }
+
+        networks.sort_by(|a, b| b.active.cmp(&a.active).then(b.strength.cmp(&a.strength)));
+        Ok(networks)
+    }
+
+    /// Saved Wi-Fi connections, by network name
+    async fn known_networks(&self) -> Result<HashMap<String, ObjectPath>, Error> {
+        let connections: Vec<ObjectPath> = self
+            .call(
+                SETTINGS_PATH,
+                "org.freedesktop.NetworkManager.Settings",
+                "ListConnections",
+                None,
+                "(ao)",
+            )
+            .await?
+            .child_value(0)
+            .get()
+            .unwrap_or_default();
+
+        let mut known = HashMap::new();
+        for connection in connections {
+            let settings: HashMap<String, HashMap<String, glib::Variant>> = self
+                .call(
+                    connection.as_str(),
+                    "org.freedesktop.NetworkManager.Settings.Connection",
+                    "GetSettings",
+                    None,
+                    "(a{sa{sv}})",
+                )
+                .await?
+                .child_value(0)
+                .get()
+                .unwrap_or_default();
+
+            let Some(ssid) = settings
+                .get("802-11-wireless")
+                .and_then(|wireless| wireless.get("ssid"))
+                .and_then(|ssid| ssid.get::<Vec<u8>>())
+            else {
+                continue;
+            };
+
+            known.insert(String::from_utf8_lossy(&ssid).to_string(), connection);
+        }
+
+        Ok(known)
+    }
+
+    /// Connects to `network`, saving it with `passphrase` if it hasn't been connected to before
+    pub async fn connect(
+        &self,
+        network: &AccessPoint,
+        passphrase: Option<&str>,
+    ) -> Result<(), Error> {
+        let device = self.device().await?;
+
+        if let Some(connection) = &network.connection {
+            self.call(
+                NM_PATH,
+                NM_BUS_NAME,
+                "ActivateConnection",
+                Some((connection.clone(), device, network.path.clone()).to_variant()),
+                "(o)",
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        let passphrase = match (network.needs_passphrase(), passphrase) {
+            (true, None) => return Err(Error::NeedsPassphrase),
+            (_, passphrase) => passphrase.unwrap_or_default(),
+        };
+
+        let mut settings: HashMap<String, HashMap<String, glib::Variant>> = HashMap::new();
+        let security = network.security.settings(passphrase)?;
+        if !security.is_empty() {
+            settings.insert("802-11-wireless-security".to_string(), security);
+        }
+
+        self.call(
+            NM_PATH,
+            NM_BUS_NAME,
+            "AddAndActivateConnection",
+            Some((settings, device, network.path.clone()).to_variant()),
+            "(oo)",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, future::Future, rc::Rc, sync::Mutex};
+
+    use super::*;
+
+    const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+
+    const INTROSPECTION: &str = r#"
+<node>
+  <interface name="org.freedesktop.NetworkManager">
+    <method name="GetDevices">
+      <arg type="ao" direction="out"/>
+    </method>
+    <method name="ActivateConnection">
+      <arg type="o" direction="in"/>
+      <arg type="o" direction="in"/>
+      <arg type="o" direction="in"/>
+      <arg type="o" direction="out"/>
+    </method>
+    <method name="AddAndActivateConnection">
+      <arg type="a{sa{sv}}" direction="in"/>
+      <arg type="o" direction="in"/>
+      <arg type="o" direction="in"/>
+      <arg type="o" direction="out"/>
+      <arg type="o" direction="out"/>
+    </method>
+    <property name="WirelessEnabled" type="b" access="read"/>
+  </interface>
+  <interface name="org.freedesktop.NetworkManager.Settings">
+    <method name="ListConnections">
+      <arg type="ao" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.NetworkManager.Device">
+    <property name="DeviceType" type="u" access="read"/>
+  </interface>
+  <interface name="org.freedesktop.NetworkManager.Device.Wireless">
+    <method name="GetAllAccessPoints">
+      <arg type="ao" direction="out"/>
+    </method>
+    <property name="ActiveAccessPoint" type="o" access="read"/>
+  </interface>
+  <interface name="org.freedesktop.NetworkManager.AccessPoint">
+    <property name="Ssid" type="ay" access="read"/>
+    <property name="Strength" type="y" access="read"/>
+    <property name="Flags" type="u" access="read"/>
+    <property name="WpaFlags" type="u" access="read"/>
+    <property name="RsnFlags" type="u" access="read"/>
+  </interface>
+</node>
+"#;
+
+    /// `GTestDBus` sets the session bus address in the environment, so only one runs at a time
+    static TEST_BUS: Mutex<()> = Mutex::new(());
+
+    /// A private bus from `GTestDBus`, which gio-rs only binds from 0.21
+    struct TestBus(*mut gio::ffi::GTestDBus);
+
+    impl TestBus {
+        fn up() -> Self {
+            unsafe {
+                let bus = gio::ffi::g_test_dbus_new(gio::ffi::G_TEST_DBUS_NONE);
+                gio::ffi::g_test_dbus_up(bus);
+                Self(bus)
+            }
+        }
+
+        fn address(&self) -> String {
+            unsafe {
+                std::ffi::CStr::from_ptr(gio::ffi::g_test_dbus_get_bus_address(self.0))
+                    .to_string_lossy()
+                    .to_string()
+            }
+        }
+
+        fn connect(&self) -> gio::DBusConnection {
+            gio::DBusConnection::for_address_sync(
+                &self.address(),
+                gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                    | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+                None,
+                None::<&gio::Cancellable>,
+            )
+            .expect("connect to the test bus")
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            unsafe {
+                gio::ffi::g_test_dbus_down(self.0);
+                glib::gobject_ffi::g_object_unref(self.0.cast());
+            }
+        }
+    }
+
+    struct FakeAccessPoint {
+        ssid: &'static str,
+        strength: u8,
+        rsn_flags: u32,
+    }
+
+    fn open(ssid: &'static str, strength: u8) -> FakeAccessPoint {
+        FakeAccessPoint {
+            ssid,
+            strength,
+            rsn_flags: 0,
+        }
+    }
+
+    fn personal(ssid: &'static str, strength: u8) -> FakeAccessPoint {
+        FakeAccessPoint {
+            ssid,
+            strength,
+            rsn_flags: KEY_MGMT_PSK,
+        }
+    }
+
+    fn enterprise(ssid: &'static str, strength: u8) -> FakeAccessPoint {
+        FakeAccessPoint {
+            ssid,
+            strength,
+            rsn_flags: KEY_MGMT_802_1X,
+        }
+    }
+
+    fn access_point_path(index: usize) -> String {
+        format!("/org/freedesktop/NetworkManager/AccessPoint/{index}")
+    }
+
+    fn object_path(path: &str) -> ObjectPath {
+        ObjectPath::try_from(path.to_string()).unwrap()
+    }
+
+    /// NetworkManager with one Wi-Fi device and no saved connections
+    struct FakeNetworkManager {
+        wifi: Wifi,
+        /// The name and arguments of each method called on NetworkManager itself
+        calls: Rc<RefCell<Vec<(String, glib::Variant)>>>,
+        _server: gio::DBusConnection,
+        _bus: TestBus,
+    }
+
+    impl FakeNetworkManager {
+        /// Exports the objects on `bus`, which has to happen on the context the test runs on
+        fn start(bus: TestBus, access_points: Vec<FakeAccessPoint>, active: Option<usize>) -> Self {
+            let server = bus.connect();
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            let paths: Vec<ObjectPath> = (0..access_points.len())
+                .map(|index| object_path(&access_point_path(index)))
+                .collect();
+
+            let recorded = calls.clone();
+            export(
+                &server,
+                NM_PATH,
+                NM_BUS_NAME,
+                move |method, args| {
+                    recorded.borrow_mut().push((method.to_string(), args));
+                    match method {
+                        "GetDevices" => (vec![object_path(DEVICE_PATH)],).to_variant(),
+                        "ActivateConnection" => (object_path(
+                            "/org/freedesktop/NetworkManager/ActiveConnection/1",
+                        ),)
+                            .to_variant(),
+                        _ => (
+                            object_path("/org/freedesktop/NetworkManager/Settings/1"),
+                            object_path("/org/freedesktop/NetworkManager/ActiveConnection/1"),
+                        )
+                            .to_variant(),
+                    }
+                },
+                |_property| true.to_variant(),
+            );
+            export(
+                &server,
+                SETTINGS_PATH,
+                "org.freedesktop.NetworkManager.Settings",
+                |_method, _args| (Vec::<ObjectPath>::new(),).to_variant(),
+                |_property| unreachable!(),
+            );
+            export(
+                &server,
+                DEVICE_PATH,
+                DEVICE,
+                |_method, _args| unreachable!(),
+                |_property| DEVICE_TYPE_WIFI.to_variant(),
+            );
+            export(
+                &server,
+                DEVICE_PATH,
+                WIRELESS,
+                move |_method, _args| (paths.clone(),).to_variant(),
+                move |_property| match active {
+                    Some(index) => object_path(&access_point_path(index)).to_variant(),
+                    None => object_path("/").to_variant(),
+                },
+            );
+            for (index, access_point) in access_points.into_iter().enumerate() {
+                export(
+                    &server,
+                    &access_point_path(index),
+                    ACCESS_POINT,
+                    |_method, _args| unreachable!(),
+                    move |property| match property {
+                        "Ssid" => access_point.ssid.as_bytes().to_variant(),
+                        "Strength" => access_point.strength.to_variant(),
+                        "Flags" if access_point.rsn_flags != 0 => AP_FLAGS_PRIVACY.to_variant(),
+                        "RsnFlags" => access_point.rsn_flags.to_variant(),
+                        _ => 0u32.to_variant(),
+                    },
+                );
+            }
+
+            server
+                .call_sync(
+                    Some("org.freedesktop.DBus"),
+                    "/org/freedesktop/DBus",
+                    "org.freedesktop.DBus",
+                    "RequestName",
+                    Some(&(NM_BUS_NAME, 0u32).to_variant()),
+                    glib::VariantTy::new("(u)").ok(),
+                    gio::DBusCallFlags::NONE,
+                    -1,
+                    None::<&gio::Cancellable>,
+                )
+                .expect("own NetworkManager's name");
+
+            Self {
+                wifi: Wifi::new(bus.connect()),
+                calls,
+                _server: server,
+                _bus: bus,
+            }
+        }
+
+        /// The arguments of each `AddAndActivateConnection` call
+        fn added(&self) -> Vec<glib::Variant> {
+            self.calls
+                .borrow()
+                .iter()
+                .filter(|(method, _args)| method == "AddAndActivateConnection")
+                .map(|(_method, args)| args.clone())
+                .collect()
+        }
+    }
+
+    fn export(
+        server: &gio::DBusConnection,
+        path: &str,
+        interface: &str,
+        method: impl Fn(&str, glib::Variant) -> glib::Variant + 'static,
+        property: impl Fn(&str) -> glib::Variant + 'static,
+    ) {
+        let info = gio::DBusNodeInfo::for_xml(INTROSPECTION)
+            .unwrap()
+            .lookup_interface(interface)
+            .unwrap();
+
+        server
+            .register_object(path, &info)
+            .method_call(
+                move |_bus, _sender, _path, _interface, name, args, invocation| {
+                    invocation.return_value(Some(&method(name, args)));
+                },
+            )
+            .property(move |_bus, _sender, _path, _interface, name| property(name))
+            .build()
+            .expect("export the object");
+    }
+
+    /// Runs `test` against a fake NetworkManager with `access_points`, the one at `active` being
+    /// connected
+    fn with_network_manager<F: Future<Output = ()>>(
+        access_points: Vec<FakeAccessPoint>,
+        active: Option<usize>,
+        test: impl FnOnce(FakeNetworkManager) -> F,
+    ) {
+        let _running = TEST_BUS.lock().unwrap_or_else(|err| err.into_inner());
+        let context = glib::MainContext::new();
+
+        context
+            .with_thread_default(|| {
+                let network_manager =
+                    FakeNetworkManager::start(TestBus::up(), access_points, active);
+                context.block_on(test(network_manager));
+            })
+            .unwrap();
+    }
+
+    fn settings(args: &glib::Variant) -> HashMap<String, HashMap<String, glib::Variant>> {
+        args.child_value(0).get().unwrap()
+    }
+
+    #[test]
+    fn lists_each_network_once_strongest_first() {
+        let access_points = vec![
+            open("Cafe", 40),
+            personal("Home", 30),
+            // hidden
+            open("", 90),
+            personal("Home", 80),
+            open("Library", 60),
+        ];
+
+        with_network_manager(access_points, None, |network_manager| async move {
+            let networks = network_manager.wifi.access_points().await.unwrap();
+
+            let listed: Vec<_> = networks
+                .iter()
+                .map(|network| (network.ssid.as_str(), network.strength))
+                .collect();
+            assert_eq!(listed, [("Home", 80), ("Library", 60), ("Cafe", 40)]);
+
+            let home = &networks[0];
+            assert_eq!(home.path.as_str(), access_point_path(3));
+            assert_eq!(home.security, Security::Personal);
+            assert!(home.connection.is_none());
+            assert!(home.needs_passphrase());
+            assert_eq!(networks[1].security, Security::Open);
+        });
+    }
+
+    #[test]
+    fn lists_the_connected_network_first() {
+        let access_points = vec![open("Strong", 90), open("Weak", 20)];
+
+        with_network_manager(access_points, Some(1), |network_manager| async move {
+            let networks = network_manager.wifi.access_points().await.unwrap();
+
+            assert_eq!(networks[0].ssid, "Weak");
+            assert!(networks[0].active);
+            assert_eq!(networks[1].ssid, "Strong");
+            assert!(!networks[1].active);
+        });
+    }
+
+    #[test]
+    fn connects_with_a_passphrase() {
+        with_network_manager(
+            vec![personal("Home", 70)],
+            None,
+            |network_manager| async move {
+                let networks = network_manager.wifi.access_points().await.unwrap();
+                network_manager
+                    .wifi
+                    .connect(&networks[0], Some("correct horse"))
+                    .await
+                    .unwrap();
+
+                let added = network_manager.added();
+                assert_eq!(added.len(), 1);
+
+                let settings = settings(&added[0]);
+                let security = &settings["802-11-wireless-security"];
+                assert_eq!(security["key-mgmt"].str(), Some("wpa-psk"));
+                assert_eq!(security["psk"].str(), Some("correct horse"));
+                assert_eq!(added[0].child_value(1).str(), Some(DEVICE_PATH));
+                assert_eq!(
+                    added[0].child_value(2).str(),
+                    Some(access_point_path(0).as_str())
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn connects_to_open_networks_without_a_passphrase() {
+        with_network_manager(vec![open("Cafe", 50)], None, |network_manager| async move {
+            let networks = network_manager.wifi.access_points().await.unwrap();
+            network_manager
+                .wifi
+                .connect(&networks[0], None)
+                .await
+                .unwrap();
+
+            let added = network_manager.added();
+            assert_eq!(added.len(), 1);
+            assert!(!settings(&added[0]).contains_key("802-11-wireless-security"));
+        });
+    }
+
+    #[test]
+    fn asks_for_a_passphrase_for_new_secured_networks() {
+        with_network_manager(
+            vec![personal("Home", 70)],
+            None,
+            |network_manager| async move {
+                let networks = network_manager.wifi.access_points().await.unwrap();
+                let result = network_manager.wifi.connect(&networks[0], None).await;
+
+                assert!(matches!(result, Err(Error::NeedsPassphrase)));
+                assert!(network_manager.added().is_empty());
+            },
+        );
+    }
+
+    #[test]
+    fn refuses_new_enterprise_networks_without_asking_for_a_passphrase() {
+        with_network_manager(
+            vec![enterprise("Campus", 70)],
+            None,
+            |network_manager| async move {
+                let networks = network_manager.wifi.access_points().await.unwrap();
+                assert_eq!(networks[0].security, Security::Enterprise);
+                assert!(!networks[0].supported());
+                assert!(!networks[0].needs_passphrase());
+
+                let result = network_manager.wifi.connect(&networks[0], None).await;
+                assert!(matches!(
+                    result,
+                    Err(Error::Unsupported(Security::Enterprise))
+                ));
+                assert!(network_manager.added().is_empty());
+            },
+        );
+    }
+}
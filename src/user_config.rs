@@ -0,0 +1,913 @@
+//! The user's `config.toml`, for everything that isn't in GSettings.
+//!
+//! Every key is optional, so an empty (or missing) file gives the defaults.
+
+use std::{
+    cell::RefCell,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+};
+
+use gtk::glib;
+use serde::Deserialize;
+
+use crate::logging;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserConfig {
+    pub cava: Cava,
+    pub player: Player,
+    pub launcher: Launcher,
+    pub clock: Clock,
+    pub battery: Battery,
+    pub sensors: Sensors,
+    pub storage: Storage,
+    pub processes: Processes,
+    /// Rules for warning about resources staying past a threshold
+    pub alerts: Vec<Alert>,
+    pub log: Log,
+    pub components: Components,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cava {
+    /// Bars in the visualiser, split evenly between both sides
+    pub bars: i32,
+    pub framerate: i32,
+}
+
+impl Default for Cava {
+    fn default() -> Self {
+        Self {
+            bars: 100,
+            framerate: 90,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Player {
+    /// Players to prefer when several are open, most preferred first.
+    /// Each is matched against part of the player's identity.
+    pub priority: Vec<String>,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            priority: vec!["Feishin".to_string(), "strawberry".to_string()],
+        }
+    }
+}
+
+impl Player {
+    /// How much `identity` is preferred over other players, 0 if it isn't in the priority list
+    pub fn rank(&self, identity: Option<&str>) -> usize {
+        identity
+            .and_then(|identity| {
+                self.priority
+                    .iter()
+                    .position(|name| identity.contains(name.as_str()))
+            })
+            .map_or(0, |position| self.priority.len() - position)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Launcher {
+    pub calculator_prefix: String,
+    pub thino_prefix: String,
+}
+
+impl Default for Launcher {
+    fn default() -> Self {
+        Self {
+            calculator_prefix: "= ".to_string(),
+            thino_prefix: "; ".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Clock {
+    /// A `GDateTime` format string, overriding `twelve_hour` and `seconds`
+    pub format: Option<String>,
+    pub twelve_hour: bool,
+    /// Without them, the clock only ticks once a minute
+    pub seconds: bool,
+    /// Time zones to show in the clock's popover, e.g. `America/New_York`
+    pub zones: Vec<String>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            format: None,
+            twelve_hour: false,
+            seconds: true,
+            zones: Vec::new(),
+        }
+    }
+}
+
+impl Clock {
+    /// The format for the bar
+    pub fn format(&self) -> String {
+        if let Some(format) = &self.format {
+            return format.clone();
+        }
+
+        format!("{} · %A %d/%m", self.time_format(self.seconds))
+    }
+
+    /// Just the time, e.g. for other time zones
+    pub fn time_format(&self, seconds: bool) -> &'static str {
+        match (self.twelve_hour, seconds) {
+            (false, false) => "%H:%M",
+            (false, true) => "%H:%M:%S",
+            (true, false) => "%-l:%M %p",
+            (true, true) => "%-l:%M:%S %p",
+        }
+    }
+
+    /// Whether the bar's clock has to tick every second
+    pub fn shows_seconds(&self) -> bool {
+        let Some(format) = &self.format else {
+            return self.seconds;
+        };
+
+        // seconds, or a combination including them
+        ["%S", "%T", "%r", "%X", "%c", "%s"]
+            .iter()
+            .any(|specifier| format.contains(specifier))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Battery {
+    /// Percentage to warn at while discharging
+    pub warning: u8,
+    /// Percentage to warn at again, urgently
+    pub critical: u8,
+    /// Send a notification at each threshold, as well as changing the battery's colour
+    pub notify: bool,
+}
+
+impl Default for Battery {
+    fn default() -> Self {
+        Self {
+            warning: 20,
+            critical: 5,
+            notify: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sensors {
+    /// Part of the label of the sensor to show in the bar, e.g. `Package id 0`.
+    /// The CPU package, or the hottest sensor, if not set.
+    pub sensor: Option<String>,
+    /// °C to warn at, for any sensor
+    pub warning: f32,
+}
+
+impl Default for Sensors {
+    fn default() -> Self {
+        Self {
+            sensor: None,
+            warning: 80.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Storage {
+    /// Mount points to show the usage of. The first is shown in the bar.
+    pub mounts: Vec<PathBuf>,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            mounts: vec![PathBuf::from("/")],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Processes {
+    /// How many of the busiest processes to list, by CPU and by memory
+    pub count: usize,
+}
+
+impl Default for Processes {
+    fn default() -> Self {
+        Self { count: 8 }
+    }
+}
+
+/// e.g. RAM above 90% for 30 seconds
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alert {
+    pub resource: Resource,
+    /// A percentage, or °C for temperatures
+    pub above: f32,
+    /// Seconds it has to stay above the threshold for
+    #[serde(default, rename = "for")]
+    pub sustain: u64,
+    /// The mount point to watch for `disk`, `/` if not set
+    #[serde(default)]
+    pub mount: Option<PathBuf>,
+    /// Part of the sensor's label for `temperature`, the one in the bar if not set
+    #[serde(default)]
+    pub sensor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    Cpu,
+    Ram,
+    Swap,
+    Disk,
+    Temperature,
+}
+
+impl Resource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resource::Cpu => "CPU",
+            Resource::Ram => "RAM",
+            Resource::Swap => "Swap",
+            Resource::Disk => "Disk",
+            Resource::Temperature => "Temperature",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Log {
+    /// `EnvFilter` directives, e.g. `info,commashell::top=debug`. `COMMASHELL_LOG` overrides this.
+    pub level: Option<String>,
+    /// Log to the systemd journal instead of stderr. Only read at startup.
+    pub journald: bool,
+}
+
+/// Which components exist on which monitors, and where they're placed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Components {
+    pub top: Layout,
+    pub right: Layout,
+    pub notifications: Layout,
+    pub launcher: Layout,
+}
+
+impl Components {
+    /// Whether the monitor with `connector` needs its windows set up again to go from `self` to
+    /// `other`
+    pub fn changed_on(&self, other: &Components, connector: Option<&str>) -> bool {
+        let layouts = [
+            (&self.top, &other.top),
+            (&self.right, &other.right),
+            (&self.notifications, &other.notifications),
+            (&self.launcher, &other.launcher),
+        ];
+
+        layouts.into_iter().any(|(old, new)| {
+            let shown = old.shown_on(connector);
+            // other monitors coming and going from the list doesn't matter here
+            let placement = |layout: &Layout| Layout {
+                monitors: None,
+                ..layout.clone()
+            };
+
+            shown != new.shown_on(connector) || (shown && placement(old) != placement(new))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    pub enabled: bool,
+    /// Connectors of the monitors to show the component on, or every monitor if not set
+    pub monitors: Option<Vec<String>>,
+    /// The edge to dock to, the component's usual edge if not set
+    pub edge: Option<Edge>,
+    pub layer: Layer,
+    /// Whether other windows should make room for the component, the component's usual
+    /// behaviour if not set. Auto-hiding panels don't by default.
+    pub exclusive: Option<bool>,
+    /// Keep the panel hidden until the pointer touches its edge
+    pub auto_hide: bool,
+    /// Milliseconds to wait after the pointer leaves before hiding again
+    pub hide_delay: u64,
+    /// Milliseconds to show an auto-hiding panel for when something happens, e.g. a new track.
+    /// 0 to never show it.
+    pub peek_duration: u64,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            monitors: None,
+            edge: None,
+            layer: Layer::Top,
+            exclusive: None,
+            auto_hide: false,
+            hide_delay: 500,
+            peek_duration: 3000,
+        }
+    }
+}
+
+impl Layout {
+    /// Whether the component should be created on the monitor with `connector`
+    pub fn shown_on(&self, connector: Option<&str>) -> bool {
+        self.enabled
+            && self.monitors.as_ref().is_none_or(|monitors| {
+                connector.is_some_and(|connector| monitors.iter().any(|m| m == connector))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Edge {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Edge::Top => "top",
+            Edge::Bottom => "bottom",
+            Edge::Left => "left",
+            Edge::Right => "right",
+        }
+    }
+
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Edge::Top | Edge::Bottom)
+    }
+}
+
+impl From<Edge> for gtk4_layer_shell::Edge {
+    fn from(edge: Edge) -> Self {
+        match edge {
+            Edge::Top => gtk4_layer_shell::Edge::Top,
+            Edge::Bottom => gtk4_layer_shell::Edge::Bottom,
+            Edge::Left => gtk4_layer_shell::Edge::Left,
+            Edge::Right => gtk4_layer_shell::Edge::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+impl From<Layer> for gtk4_layer_shell::Layer {
+    fn from(layer: Layer) -> Self {
+        match layer {
+            Layer::Background => gtk4_layer_shell::Layer::Background,
+            Layer::Bottom => gtk4_layer_shell::Layer::Bottom,
+            Layer::Top => gtk4_layer_shell::Layer::Top,
+            Layer::Overlay => gtk4_layer_shell::Layer::Overlay,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read(io::Error),
+    Parse(toml::de::Error),
+    Invalid { key: &'static str, reason: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(err) => write!(f, "could not read the file: {err}"),
+            Error::Parse(err) => write!(f, "{}", err.to_string().trim_end()),
+            Error::Invalid { key, reason } => write!(f, "invalid `{key}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn invalid(key: &'static str, reason: impl Into<String>) -> Error {
+    Error::Invalid {
+        key,
+        reason: reason.into(),
+    }
+}
+
+impl UserConfig {
+    pub fn path() -> PathBuf {
+        glib::user_config_dir()
+            .join("commashell")
+            .join("config.toml")
+    }
+
+    /// Loads the config at `path`, or the defaults if there's no file there
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Read(err)),
+        };
+
+        text.parse()
+    }
+
+    /// Replaces `current` with the config at `path`, returning the one it replaced. `current` is
+    /// left as it is if the new config can't be loaded.
+    pub fn reload(current: &RefCell<Rc<Self>>, path: &Path) -> Result<Rc<Self>, Error> {
+        let config = Self::load(path)?;
+        Ok(current.replace(Rc::new(config)))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if !(2..=512).contains(&self.cava.bars) || self.cava.bars % 2 != 0 {
+            return Err(invalid(
+                "cava.bars",
+                "must be an even number between 2 and 512",
+            ));
+        }
+
+        if !(1..=360).contains(&self.cava.framerate) {
+            return Err(invalid("cava.framerate", "must be between 1 and 360"));
+        }
+
+        if self.player.priority.iter().any(String::is_empty) {
+            return Err(invalid("player.priority", "player names can't be empty"));
+        }
+
+        if self.launcher.calculator_prefix.is_empty() {
+            return Err(invalid("launcher.calculator_prefix", "can't be empty"));
+        }
+
+        if self.launcher.thino_prefix.is_empty() {
+            return Err(invalid("launcher.thino_prefix", "can't be empty"));
+        }
+
+        if self.launcher.calculator_prefix == self.launcher.thino_prefix {
+            return Err(invalid(
+                "launcher.thino_prefix",
+                "must be different from launcher.calculator_prefix",
+            ));
+        }
+
+        let format = self.clock.format();
+        if format.is_empty()
+            || glib::DateTime::now_local()
+                .and_then(|now| now.format(&format))
+                .is_err()
+        {
+            return Err(invalid(
+                "clock.format",
+                "not a valid format, see https://docs.gtk.org/glib/method.DateTime.format.html",
+            ));
+        }
+
+        if let Some(zone) = self
+            .clock
+            .zones
+            .iter()
+            .find(|zone| glib::TimeZone::from_identifier(Some(zone.as_str())).is_none())
+        {
+            return Err(invalid(
+                "clock.zones",
+                format!("{zone} is not a time zone, e.g. Europe/London"),
+            ));
+        }
+
+        if self.battery.warning > 100 {
+            return Err(invalid("battery.warning", "must be a percentage"));
+        }
+
+        if self.battery.critical >= self.battery.warning {
+            return Err(invalid(
+                "battery.critical",
+                "must be lower than battery.warning",
+            ));
+        }
+
+        if self
+            .sensors
+            .sensor
+            .as_ref()
+            .is_some_and(|sensor| sensor.is_empty())
+        {
+            return Err(invalid("sensors.sensor", "can't be empty"));
+        }
+
+        if !(1.0..=150.0).contains(&self.sensors.warning) {
+            return Err(invalid("sensors.warning", "must be between 1 and 150 °C"));
+        }
+
+        if self.storage.mounts.iter().any(|mount| !mount.is_absolute()) {
+            return Err(invalid("storage.mounts", "must be absolute paths"));
+        }
+
+        if !(1..=50).contains(&self.processes.count) {
+            return Err(invalid("processes.count", "must be between 1 and 50"));
+        }
+
+        for alert in &self.alerts {
+            let percentage = alert.resource != Resource::Temperature;
+            if alert.above <= 0.0 || (percentage && alert.above >= 100.0) {
+                return Err(invalid(
+                    "alerts.above",
+                    format!(
+                        "{} is out of range for {}",
+                        alert.above,
+                        alert.resource.name()
+                    ),
+                ));
+            }
+
+            if alert.mount.is_some() && alert.resource != Resource::Disk {
+                return Err(invalid("alerts.mount", "only applies to disk alerts"));
+            }
+
+            if alert
+                .mount
+                .as_ref()
+                .is_some_and(|mount| !mount.is_absolute())
+            {
+                return Err(invalid("alerts.mount", "must be an absolute path"));
+            }
+
+            if alert.sensor.is_some() && alert.resource != Resource::Temperature {
+                return Err(invalid(
+                    "alerts.sensor",
+                    "only applies to temperature alerts",
+                ));
+            }
+        }
+
+        let components = &self.components;
+        let edges = [
+            (
+                "components.top.edge",
+                &components.top,
+                &[Edge::Top, Edge::Bottom][..],
+            ),
+            (
+                "components.right.edge",
+                &components.right,
+                &[Edge::Left, Edge::Right],
+            ),
+            (
+                "components.notifications.edge",
+                &components.notifications,
+                &[Edge::Left, Edge::Right],
+            ),
+            ("components.launcher.edge", &components.launcher, &[]),
+        ];
+
+        for (key, layout, allowed) in edges {
+            let Some(edge) = layout.edge else {
+                continue;
+            };
+
+            if !allowed.contains(&edge) {
+                return Err(invalid(
+                    key,
+                    match allowed {
+                        [] => "the launcher is always centred".to_string(),
+                        allowed => format!(
+                            "must be {}",
+                            allowed
+                                .iter()
+                                .map(|edge| edge.name())
+                                .collect::<Vec<_>>()
+                                .join(" or ")
+                        ),
+                    },
+                ));
+            }
+        }
+
+        if components.notifications.auto_hide {
+            return Err(invalid(
+                "components.notifications.auto_hide",
+                "only the top bar and music panel can auto-hide",
+            ));
+        }
+
+        if components.launcher.auto_hide {
+            return Err(invalid(
+                "components.launcher.auto_hide",
+                "only the top bar and music panel can auto-hide",
+            ));
+        }
+
+        if let Some(Err(err)) = self.log.level.as_deref().map(logging::parse) {
+            return Err(invalid("log.level", err));
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for UserConfig {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let config: Self = toml::from_str(text).map_err(Error::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config file in the temporary directory, removed when dropped
+    struct ConfigFile(PathBuf);
+
+    impl ConfigFile {
+        fn new(name: &str, text: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("commashell-{}-{name}.toml", std::process::id()));
+            fs::write(&path, text).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, text: &str) {
+            fs::write(&self.0, text).unwrap();
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// The key `text` is rejected for
+    fn invalid_key(text: &str) -> &'static str {
+        match text.parse::<UserConfig>() {
+            Err(Error::Invalid { key, .. }) => key,
+            other => panic!("{text:?} gave {other:?}"),
+        }
+    }
+
+    #[test]
+    fn loads_a_valid_config() {
+        let config: UserConfig = r#"
+            alerts = [
+                { resource = "ram", above = 90, for = 30 },
+                { resource = "disk", above = 95, mount = "/home" },
+                { resource = "temperature", above = 95, sensor = "Tctl" },
+            ]
+
+            [cava]
+            bars = 50
+            framerate = 60
+
+            [player]
+            priority = ["spotify"]
+
+            [launcher]
+            calculator_prefix = "="
+            thino_prefix = ";"
+
+            [clock]
+            twelve_hour = true
+            seconds = false
+            zones = ["Europe/London", "America/New_York"]
+
+            [battery]
+            warning = 30
+            critical = 10
+            notify = false
+
+            [sensors]
+            sensor = "Package id 0"
+            warning = 90
+
+            [storage]
+            mounts = ["/", "/home"]
+
+            [processes]
+            count = 5
+
+            [log]
+            level = "info,commashell::top=debug"
+
+            [components.top]
+            edge = "bottom"
+            auto_hide = true
+
+            [components.right]
+            monitors = ["DP-2"]
+            layer = "overlay"
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(config.cava.bars, 50);
+        assert_eq!(config.player.priority, ["spotify"]);
+        assert_eq!(config.launcher.calculator_prefix, "=");
+        assert_eq!(config.clock.time_format(config.clock.seconds), "%-l:%M %p");
+        assert_eq!(config.clock.zones.len(), 2);
+        assert!(!config.battery.notify);
+        assert_eq!(config.sensors.sensor.as_deref(), Some("Package id 0"));
+        assert_eq!(config.storage.mounts[1], Path::new("/home"));
+        assert_eq!(config.processes.count, 5);
+        assert_eq!(config.alerts.len(), 3);
+        assert_eq!(config.alerts[0].sustain, 30);
+        assert_eq!(config.components.top.edge, Some(Edge::Bottom));
+        assert!(config.components.top.auto_hide);
+        assert!(config.components.right.shown_on(Some("DP-2")));
+        assert!(!config.components.right.shown_on(Some("HDMI-A-1")));
+        assert_eq!(config.components.right.layer, Layer::Overlay);
+    }
+
+    #[test]
+    fn gives_the_defaults_for_an_empty_file() {
+        let file = ConfigFile::new("empty", "");
+        let config = UserConfig::load(&file.0).unwrap();
+
+        assert_eq!(config.cava.bars, Cava::default().bars);
+        assert_eq!(config.player.priority, Player::default().priority);
+        assert_eq!(config.battery.warning, 20);
+        assert_eq!(config.storage.mounts, [PathBuf::from("/")]);
+        assert_eq!(config.processes.count, 8);
+        assert!(config.alerts.is_empty());
+        assert_eq!(config.components, Components::default());
+    }
+
+    #[test]
+    fn gives_the_defaults_without_a_file() {
+        let path = std::env::temp_dir().join("commashell-missing/config.toml");
+        let config = UserConfig::load(&path).unwrap();
+
+        assert_eq!(config.cava.framerate, Cava::default().framerate);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for (text, key) in [
+            ("cava.bars = 7", "cava.bars"),
+            ("cava.bars = 0", "cava.bars"),
+            ("cava.bars = 1024", "cava.bars"),
+            ("cava.framerate = 0", "cava.framerate"),
+            (r#"player.priority = ["mpv", ""]"#, "player.priority"),
+            (
+                r#"launcher.calculator_prefix = """#,
+                "launcher.calculator_prefix",
+            ),
+            (r#"launcher.thino_prefix = """#, "launcher.thino_prefix"),
+            (r#"launcher.thino_prefix = "= ""#, "launcher.thino_prefix"),
+            (r#"clock.format = """#, "clock.format"),
+            (r#"clock.zones = ["Mars/Olympus_Mons"]"#, "clock.zones"),
+            ("battery.warning = 101", "battery.warning"),
+            ("battery.critical = 20", "battery.critical"),
+            (r#"sensors.sensor = """#, "sensors.sensor"),
+            ("sensors.warning = 0", "sensors.warning"),
+            ("sensors.warning = 200", "sensors.warning"),
+            (r#"storage.mounts = ["home"]"#, "storage.mounts"),
+            ("processes.count = 0", "processes.count"),
+            ("processes.count = 100", "processes.count"),
+            (
+                r#"alerts = [{ resource = "ram", above = 100 }]"#,
+                "alerts.above",
+            ),
+            (
+                r#"alerts = [{ resource = "temperature", above = 0 }]"#,
+                "alerts.above",
+            ),
+            (
+                r#"alerts = [{ resource = "cpu", above = 90, mount = "/" }]"#,
+                "alerts.mount",
+            ),
+            (
+                r#"alerts = [{ resource = "disk", above = 90, mount = "home" }]"#,
+                "alerts.mount",
+            ),
+            (
+                r#"alerts = [{ resource = "ram", above = 90, sensor = "Tctl" }]"#,
+                "alerts.sensor",
+            ),
+            (r#"components.top.edge = "left""#, "components.top.edge"),
+            (r#"components.right.edge = "top""#, "components.right.edge"),
+            (
+                r#"components.notifications.edge = "bottom""#,
+                "components.notifications.edge",
+            ),
+            (
+                r#"components.launcher.edge = "top""#,
+                "components.launcher.edge",
+            ),
+            (
+                "components.notifications.auto_hide = true",
+                "components.notifications.auto_hide",
+            ),
+            (
+                "components.launcher.auto_hide = true",
+                "components.launcher.auto_hide",
+            ),
+            (r#"log.level = "commashell=loud""#, "log.level"),
+        ] {
+            assert_eq!(invalid_key(text), key, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_wrong_types() {
+        for text in [
+            "colour = \"red\"",
+            "[cava]\nbar = 10",
+            "cava.bars = \"lots\"",
+            r#"alerts = [{ resource = "gpu", above = 90 }]"#,
+            r#"components.top.edge = "middle""#,
+        ] {
+            assert!(
+                matches!(text.parse::<UserConfig>(), Err(Error::Parse(_))),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_toml_syntax_errors() {
+        let file = ConfigFile::new("syntax", "[cava\nbars = 10");
+
+        assert!(matches!(UserConfig::load(&file.0), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn keeps_the_previous_config_when_a_reload_fails() {
+        let file = ConfigFile::new("reload", "cava.bars = 40");
+        let current = RefCell::new(Rc::new(UserConfig::default()));
+
+        let old = UserConfig::reload(&current, &file.0).unwrap();
+        assert_eq!(old.cava.bars, Cava::default().bars);
+        assert_eq!(current.borrow().cava.bars, 40);
+
+        file.write("cava.bars = 41");
+        assert!(matches!(
+            UserConfig::reload(&current, &file.0),
+            Err(Error::Invalid {
+                key: "cava.bars",
+                ..
+            })
+        ));
+        assert_eq!(current.borrow().cava.bars, 40);
+
+        file.write("[cava");
+        assert!(UserConfig::reload(&current, &file.0).is_err());
+        assert_eq!(current.borrow().cava.bars, 40);
+    }
+
+    #[test]
+    fn only_rebuilds_monitors_whose_components_changed() {
+        let old = Components::default();
+        let mut new = old.clone();
+        new.right.monitors = Some(vec!["DP-2".to_string()]);
+
+        assert!(!old.changed_on(&new, Some("DP-2")));
+        assert!(old.changed_on(&new, Some("HDMI-A-1")));
+
+        let mut new = old.clone();
+        new.top.edge = Some(Edge::Bottom);
+        assert!(old.changed_on(&new, Some("DP-2")));
+
+        let mut hidden = old.clone();
+        hidden.top.enabled = false;
+        let mut moved = hidden.clone();
+        moved.top.edge = Some(Edge::Bottom);
+        assert!(!hidden.changed_on(&moved, Some("DP-2")));
+    }
+}